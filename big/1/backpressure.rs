@@ -0,0 +1,216 @@
+use assignment_1_solution::System;
+use assignment_1_test_utils::adapter::ModuleSystem;
+use assignment_1_test_utils::handler;
+use assignment_1_test_utils::modules::DropCounter;
+use assignment_1_test_utils::queues::{GaugedRef, Parcel, QueueGauge, QueueMonitor, SlowSink};
+use ntest::timeout;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+// Mailboxes are unbounded: `send` never waits for a slow module, so its queue,
+// and the memory it holds, grow as long as it is flooded. These tests observe
+// the queue and the memory of the process over time, print them with
+// `--nocapture`, and check that queued messages are freed once the system is
+// shut down. They run on the paused clock, so the slow module takes no real
+// time.
+
+const PRODUCERS: u64 = 8;
+const PARCELS_PER_PRODUCER: u64 = 500;
+const PARCELS: u64 = PRODUCERS * PARCELS_PER_PRODUCER;
+const PAYLOAD_BYTES: usize = 4096;
+const HANDLE_TIME: Duration = Duration::from_millis(1);
+
+async fn register_sink(system: &mut System, handle_time: Duration) -> GaugedRef<SlowSink> {
+    let gauge = QueueGauge::new();
+    let module_ref = system
+        .register(SlowSink {
+            handle_time,
+            gauge: gauge.clone(),
+        })
+        .await;
+    GaugedRef { module_ref, gauge }
+}
+
+fn parcel(drop_counter: &DropCounter) -> Parcel {
+    Parcel {
+        /* Not zeroed, so that the pages are really allocated */
+        payload: vec![1; PAYLOAD_BYTES],
+        counted: drop_counter.message(),
+    }
+}
+
+/// Waits up to `grace` for `expected` drops, as a solution may free the
+/// queue of a module only after its task is gone.
+async fn wait_for_drops(drop_counter: &DropCounter, expected: u64, grace: Duration) -> u64 {
+    let deadline = Instant::now() + grace;
+    while drop_counter.dropped() < expected && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    drop_counter.dropped()
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(10000)]
+async fn queue_of_slow_module_grows_under_flood_and_drains() {
+    let mut system = System::new().await;
+    let sink = register_sink(&mut system, HANDLE_TIME).await;
+    let drop_counter = DropCounter::new();
+    let monitor = QueueMonitor::start(sink.gauge.clone(), Duration::from_millis(100));
+
+    let mut producers = Vec::new();
+    for _ in 0..PRODUCERS {
+        let sink = sink.clone();
+        let drop_counter = drop_counter.clone();
+        producers.push(tokio::spawn(async move {
+            for _ in 0..PARCELS_PER_PRODUCER {
+                sink.send(parcel(&drop_counter)).await;
+                tokio::task::yield_now().await;
+            }
+        }));
+    }
+    for producer in producers {
+        producer.await.unwrap();
+    }
+
+    /* The producers never waited, so the clock did not move */
+    let flooded = monitor.sample_now();
+    let flooded_idx = monitor.samples().len() - 1;
+    assert!(
+        flooded.queue_len >= PARCELS - PRODUCERS,
+        "Only {} of {} parcels queued",
+        flooded.queue_len,
+        PARCELS
+    );
+    let queued_payload = PARCELS * PAYLOAD_BYTES as u64;
+    assert!(
+        flooded.rss_bytes <= monitor.baseline().rss_bytes + 4 * queued_payload + (64 << 20),
+        "RSS grew by {} bytes for {} bytes of queued payload",
+        flooded.rss_bytes - monitor.baseline().rss_bytes,
+        queued_payload
+    );
+
+    while !sink.gauge.is_empty() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let drained_at = monitor.sample_now().at;
+    assert!(drained_at >= HANDLE_TIME * PARCELS as u32);
+    assert_eq!(sink.gauge.handled(), PARCELS);
+    assert_eq!(drop_counter.dropped(), PARCELS);
+    let queue_lens: Vec<u64> = monitor
+        .samples()
+        .iter()
+        .skip(flooded_idx)
+        .map(|sample| sample.queue_len)
+        .collect();
+    assert!(
+        queue_lens.windows(2).all(|pair| pair[0] >= pair[1]),
+        "Queue grew after the flood: {:?}",
+        queue_lens
+    );
+
+    monitor.report();
+    system.shutdown().await;
+}
+
+/// Sends parcels to the sink from its handler.
+struct Producer {
+    sink: GaugedRef<SlowSink>,
+    drop_counter: DropCounter,
+}
+
+struct Produce(u64);
+
+/// Asks for the length of the queue of the sink.
+struct QueueLen(oneshot::Sender<u64>);
+
+handler!(Producer, Produce, |self, msg| {
+    for _ in 0..msg.0 {
+        self.sink.send(parcel(&self.drop_counter)).await;
+    }
+});
+
+handler!(Producer, QueueLen, |self, msg| {
+    msg.0.send(self.sink.gauge.len()).unwrap();
+});
+
+#[tokio::test(start_paused = true)]
+#[timeout(5000)]
+async fn producing_module_is_not_held_back_by_slow_module() {
+    let mut system = System::new().await;
+    let sink = register_sink(&mut system, HANDLE_TIME).await;
+    let drop_counter = DropCounter::new();
+    let producer = system
+        .register(Producer {
+            sink: sink.clone(),
+            drop_counter: drop_counter.clone(),
+        })
+        .await;
+    let start = Instant::now();
+
+    producer.send(Produce(PARCELS)).await;
+    let (len_sender, len_receiver) = oneshot::channel();
+    producer.send(QueueLen(len_sender)).await;
+    let queue_len = len_receiver.await.unwrap();
+
+    /* Handling them would take PARCELS * HANDLE_TIME */
+    assert!(start.elapsed() <= HANDLE_TIME * 10);
+    assert!(
+        queue_len >= PARCELS - 10,
+        "Only {} of {} parcels queued",
+        queue_len,
+        PARCELS
+    );
+
+    drop(producer);
+    drop(sink);
+    system.shutdown().await;
+    assert_eq!(
+        wait_for_drops(&drop_counter, PARCELS, Duration::from_millis(100)).await,
+        PARCELS
+    );
+}
+
+async fn fill_queue_of_stuck_module(
+    system: &mut System,
+    drop_counter: &DropCounter,
+) -> GaugedRef<SlowSink> {
+    let sink = register_sink(system, Duration::from_secs(3600)).await;
+    for _ in 0..PARCELS {
+        sink.send(parcel(drop_counter)).await;
+    }
+    /* Let the first parcel get stuck in the handler */
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert_eq!(drop_counter.dropped(), 0);
+    sink
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(5000)]
+async fn queued_messages_are_freed_when_refs_are_dropped_before_shutdown() {
+    let mut system = System::new().await;
+    let drop_counter = DropCounter::new();
+    let sink = fill_queue_of_stuck_module(&mut system, &drop_counter).await;
+
+    drop(sink);
+    system.shutdown().await;
+    assert_eq!(
+        wait_for_drops(&drop_counter, PARCELS, Duration::from_millis(100)).await,
+        PARCELS
+    );
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(5000)]
+async fn queued_messages_are_freed_when_refs_are_dropped_after_shutdown() {
+    let mut system = System::new().await;
+    let drop_counter = DropCounter::new();
+    let sink = fill_queue_of_stuck_module(&mut system, &drop_counter).await;
+
+    system.shutdown().await;
+    drop(sink);
+    assert_eq!(
+        wait_for_drops(&drop_counter, PARCELS, Duration::from_millis(100)).await,
+        PARCELS
+    );
+}
@@ -0,0 +1,33 @@
+use assignment_1_test_utils::bench::{flood, ping_pong, register, timers};
+use ntest::timeout;
+use std::time::Duration;
+
+// Smoke test of the benchmarks at tiny sizes, the full ones are run with
+// `cargo run --release --bin bench` in test-utils, see README.md.
+
+#[tokio::test]
+#[timeout(5000)]
+async fn benchmarks_report_one_json_object_each() {
+    let results = [
+        register(100).await,
+        flood(10, 1000, 2).await,
+        ping_pong(3, 100).await,
+        timers(2, 10, Duration::from_millis(10), Duration::from_millis(100)).await,
+    ];
+
+    for (result, benchmark) in results
+        .iter()
+        .zip(["register", "flood", "ping_pong", "timers"])
+    {
+        let line = result.to_string();
+        assert!(
+            line.starts_with(&format!("{{\"benchmark\":\"{}\",", benchmark)),
+            "{}",
+            line
+        );
+        assert!(line.ends_with('}') && !line.contains('\n'), "{}", line);
+        assert!(line.contains("\"per_sec\":"), "{}", line);
+    }
+    assert!(results[2].to_string().contains("\"p99_us\":"));
+    assert!(results[3].to_string().contains("\"delivered_ratio\":"));
+}
@@ -0,0 +1,165 @@
+use assignment_1_solution::System;
+use assignment_1_test_utils::adapter::{ModuleSystem, SelfRef};
+use assignment_1_test_utils::modules::Tick;
+use assignment_1_test_utils::spy::Spy;
+use assignment_1_test_utils::{handler, with_self_ref};
+use ntest::timeout;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+// A module which keeps sending messages to itself, like `CountToFive` without
+// the stop, must not starve the other modules: their messages and ticks are
+// handled within LATENCY_BOUND. These tests run in real time, as tokio's
+// paused clock never advances while the flooding module keeps it busy.
+
+const LATENCY_BOUND: Duration = Duration::from_millis(100);
+const FLOODERS: usize = 4;
+
+#[derive(Clone)]
+struct Flood;
+
+struct Flooder {
+    self_ref: SelfRef<Flooder>,
+    handled: Arc<AtomicU64>,
+}
+
+with_self_ref!(Flooder, self_ref);
+
+handler!(Flooder, Flood, |self, self_ref, _msg| {
+    self.handled.fetch_add(1, Ordering::Relaxed);
+    self_ref.send(Flood).await;
+});
+
+/// Starts `FLOODERS` flooding modules, returns the count of their handled
+/// messages.
+async fn start_flooders(system: &mut System) -> Arc<AtomicU64> {
+    let handled = Arc::new(AtomicU64::new(0));
+    for _ in 0..FLOODERS {
+        let flooder = system
+            .register_with_self_ref(Flooder {
+                self_ref: SelfRef::default(),
+                handled: handled.clone(),
+            })
+            .await;
+        flooder.send(Flood).await;
+    }
+    /* Let the flood get going */
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    handled
+}
+
+async fn peers_messages_are_handled_within_bound() {
+    let mut system = System::new().await;
+    let flooded = start_flooders(&mut system).await;
+    let mut peers = Vec::new();
+    for _ in 0..3 {
+        peers.push(Spy::<u32>::register(&mut system).await);
+    }
+
+    for round in 0..5 {
+        for (peer_ref, spy) in &mut peers {
+            let sent_at = Instant::now();
+            peer_ref.send(round).await;
+            assert_eq!(spy.recv_timeout(LATENCY_BOUND).await, Some(round));
+            assert!(sent_at.elapsed() <= LATENCY_BOUND);
+        }
+    }
+
+    let flooded_before = flooded.load(Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(flooded.load(Ordering::Relaxed) > flooded_before);
+    system.shutdown().await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+#[timeout(5000)]
+async fn peers_messages_are_handled_within_bound_on_current_thread() {
+    peers_messages_are_handled_within_bound().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(5000)]
+async fn peers_messages_are_handled_within_bound_on_multi_thread() {
+    peers_messages_are_handled_within_bound().await;
+}
+
+async fn peers_timers_tick_within_bound() {
+    let mut system = System::new().await;
+    let _flooded = start_flooders(&mut system).await;
+    let (peer_ref, mut spy) = Spy::<Tick>::register(&mut system).await;
+    let interval = Duration::from_millis(20);
+
+    let timer_handle = peer_ref.request_tick(Tick, interval).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    timer_handle.stop().await;
+
+    let mut ticked_at: Vec<Duration> = spy.received_at().into_iter().map(|(at, _)| at).collect();
+    ticked_at.insert(0, Duration::ZERO);
+    let max_gap = ticked_at
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .max()
+        .unwrap();
+    assert!(
+        ticked_at.len() > 10,
+        "Only {} of 25 ticks arrived",
+        ticked_at.len() - 1
+    );
+    assert!(
+        max_gap <= interval + LATENCY_BOUND,
+        "Ticks {:?} apart",
+        max_gap
+    );
+    system.shutdown().await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+#[timeout(5000)]
+async fn peers_timers_tick_within_bound_on_current_thread() {
+    peers_timers_tick_within_bound().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(5000)]
+async fn peers_timers_tick_within_bound_on_multi_thread() {
+    peers_timers_tick_within_bound().await;
+}
+
+struct TickedFlooder {
+    self_ref: SelfRef<TickedFlooder>,
+    ticks: Arc<AtomicU64>,
+}
+
+with_self_ref!(TickedFlooder, self_ref);
+
+handler!(TickedFlooder, Flood, |self, self_ref, _msg| {
+    self_ref.send(Flood).await;
+});
+
+handler!(TickedFlooder, Tick, |self, _msg| {
+    self.ticks.fetch_add(1, Ordering::Relaxed);
+});
+
+#[tokio::test(flavor = "current_thread")]
+#[timeout(5000)]
+async fn flooding_module_still_gets_its_own_ticks() {
+    let mut system = System::new().await;
+    let ticks = Arc::new(AtomicU64::new(0));
+    let flooder = system
+        .register_with_self_ref(TickedFlooder {
+            self_ref: SelfRef::default(),
+            ticks: ticks.clone(),
+        })
+        .await;
+
+    flooder.send(Flood).await;
+    let timer_handle = flooder.request_tick(Tick, Duration::from_millis(20)).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    timer_handle.stop().await;
+
+    /* Its queue holds at most one flood message, so ticks queue right behind */
+    assert!(ticks.load(Ordering::Relaxed) > 10);
+    system.shutdown().await;
+}
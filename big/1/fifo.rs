@@ -0,0 +1,182 @@
+use assignment_1_solution::{ModuleRef, System};
+use assignment_1_test_utils::adapter::ModuleSystem;
+use assignment_1_test_utils::handler;
+use assignment_1_test_utils::modules::Recorder;
+use proptest::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::runtime::Builder;
+
+// Messages of one sender, be it a task with a cloned `ModuleRef` or a module
+// sending from its handler, are handled in the order they were sent, each
+// exactly once, however they interleave with the messages of other senders.
+// proptest picks the senders, where they yield and the runtime flavor.
+
+#[derive(Clone, Debug)]
+struct Numbered {
+    sender: usize,
+    seq: u32,
+}
+
+/// How one sender sends its messages: in bursts, yielding after every
+/// message marked `true` and between the bursts.
+#[derive(Clone, Debug)]
+struct SenderPlan {
+    from_module: bool,
+    bursts: Vec<Vec<bool>>,
+}
+
+impl SenderPlan {
+    fn messages(&self) -> u32 {
+        self.bursts.iter().map(Vec::len).sum::<usize>() as u32
+    }
+}
+
+/// Makes `Relay` send one burst of messages from its handler.
+struct Burst(Vec<bool>);
+
+/// Sends numbered messages of its sender to the recorder, from its handler.
+struct Relay {
+    sender: usize,
+    next_seq: u32,
+    recorder: ModuleRef<Recorder<Numbered>>,
+}
+
+handler!(Relay, Burst, |self, msg| {
+    for yield_after in msg.0 {
+        self.recorder
+            .send(Numbered {
+                sender: self.sender,
+                seq: self.next_seq,
+            })
+            .await;
+        self.next_seq += 1;
+        if yield_after {
+            tokio::task::yield_now().await;
+        }
+    }
+});
+
+async fn send_from_task(sender: usize, plan: SenderPlan, recorder: ModuleRef<Recorder<Numbered>>) {
+    let mut seq = 0;
+    for burst in plan.bursts {
+        for yield_after in burst {
+            recorder.send(Numbered { sender, seq }).await;
+            seq += 1;
+            if yield_after {
+                tokio::task::yield_now().await;
+            }
+        }
+        tokio::task::yield_now().await;
+    }
+}
+
+async fn send_from_module(plan: SenderPlan, relay: ModuleRef<Relay>) {
+    for burst in plan.bursts {
+        relay.send(Burst(burst)).await;
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Runs the senders on a current thread runtime, or a multi thread one with
+/// `worker_threads`, and returns the sequence numbers handled per sender.
+fn run_senders(plans: &[SenderPlan], worker_threads: Option<usize>) -> HashMap<usize, Vec<u32>> {
+    let mut builder = match worker_threads {
+        None => Builder::new_current_thread(),
+        Some(worker_threads) => {
+            let mut builder = Builder::new_multi_thread();
+            builder.worker_threads(worker_threads);
+            builder
+        }
+    };
+    let runtime = builder.enable_all().build().unwrap();
+
+    runtime.block_on(async {
+        let mut system = System::new().await;
+        let (recorder, recording) = Recorder::new();
+        let recorder = system.register(recorder).await;
+
+        let mut senders = Vec::new();
+        for (sender, plan) in plans.iter().cloned().enumerate() {
+            if plan.from_module {
+                let relay = system
+                    .register(Relay {
+                        sender,
+                        next_seq: 0,
+                        recorder: recorder.clone(),
+                    })
+                    .await;
+                senders.push(tokio::spawn(send_from_module(plan, relay)));
+            } else {
+                senders.push(tokio::spawn(send_from_task(sender, plan, recorder.clone())));
+            }
+        }
+        for sender in senders {
+            sender.await.unwrap();
+        }
+
+        /* Relays may still be sending; wait a bit longer to catch duplicates */
+        let sent: usize = plans.iter().map(|plan| plan.messages() as usize).sum();
+        let _ = tokio::time::timeout(Duration::from_secs(5), async {
+            while recording.len() < sent {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        system.shutdown().await;
+
+        let mut handled: HashMap<usize, Vec<u32>> = HashMap::new();
+        for msg in recording.messages() {
+            handled.entry(msg.sender).or_default().push(msg.seq);
+        }
+        handled
+    })
+}
+
+fn sender_plan() -> impl Strategy<Value = SenderPlan> {
+    (
+        any::<bool>(),
+        prop::collection::vec(prop::collection::vec(any::<bool>(), 1..20), 1..5),
+    )
+        .prop_map(|(from_module, bursts)| SenderPlan {
+            from_module,
+            bursts,
+        })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn per_sender_order_is_preserved(
+        plans in prop::collection::vec(sender_plan(), 1..16),
+        worker_threads in prop::option::of(1..=4usize),
+    ) {
+        let handled = run_senders(&plans, worker_threads);
+
+        for (sender, plan) in plans.iter().enumerate() {
+            let expected: Vec<u32> = (0..plan.messages()).collect();
+            prop_assert_eq!(handled.get(&sender).cloned().unwrap_or_default(), expected);
+        }
+        prop_assert!(handled.keys().all(|&sender| sender < plans.len()));
+    }
+}
+
+#[test]
+fn per_sender_order_is_preserved_with_many_senders() {
+    let plans: Vec<SenderPlan> = (0..64)
+        .map(|sender| SenderPlan {
+            from_module: sender % 2 == 0,
+            bursts: vec![(0..100).map(|seq| seq % (sender + 2) == 0).collect(); 10],
+        })
+        .collect();
+
+    for worker_threads in [None, Some(4)] {
+        let handled = run_senders(&plans, worker_threads);
+        for (sender, plan) in plans.iter().enumerate() {
+            let expected: Vec<u32> = (0..plan.messages()).collect();
+            assert_eq!(handled[&sender], expected, "Sender {}", sender);
+        }
+    }
+}
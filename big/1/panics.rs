@@ -0,0 +1,204 @@
+#![cfg(tokio_unstable)]
+
+use assignment_1_solution::{ModuleRef, System};
+use assignment_1_test_utils::adapter::ModuleSystem;
+use assignment_1_test_utils::handler;
+use assignment_1_test_utils::leaks::run_leak_checked;
+use assignment_1_test_utils::modules::{Counted, DropCounter};
+use assignment_1_test_utils::spy::Spy;
+use ntest::timeout;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+// What happens when a handler panics. A module whose handler panicked is in an
+// unknown state, so it is stopped:
+//  - it handles no more messages and is dropped,
+//  - its queued messages, and the ones sent to it later, are dropped unhandled,
+//  - sending to it and requesting ticks from it does not panic,
+//  - other modules and their timers keep working,
+//  - `System::shutdown` completes, also while a handler is about to panic.
+// The panic must not reach the runtime: on the current thread runtime the
+// tests run with `unhandled_panic = "shutdown_runtime"`, which would abort
+// them. Every test also checks that no task is left alive, which needs
+// tokio_unstable, see README.md.
+
+const GRACE: Duration = Duration::from_millis(200);
+
+/// Message on which `Fragile` panics.
+#[derive(Clone)]
+struct Explode;
+
+/// Message on which `Fragile` panics after sleeping for the given time.
+struct ExplodeAfter(Duration);
+
+/// Forwards the numbers it handles and panics on `Explode`.
+struct Fragile {
+    handled_sender: UnboundedSender<u32>,
+}
+
+handler!(Fragile, u32, |self, msg| {
+    self.handled_sender.send(msg).unwrap();
+});
+
+handler!(Fragile, Explode, |self, _msg| {
+    panic!("Fragile exploded");
+});
+
+handler!(Fragile, ExplodeAfter, |self, msg| {
+    tokio::time::sleep(msg.0).await;
+    panic!("Fragile exploded after {:?}", msg.0);
+});
+
+handler!(Fragile, Counted, |self, _msg| {});
+
+async fn register_fragile(system: &mut System) -> (ModuleRef<Fragile>, UnboundedReceiver<u32>) {
+    let (handled_sender, handled_receiver) = unbounded_channel();
+    (
+        system.register(Fragile { handled_sender }).await,
+        handled_receiver,
+    )
+}
+
+/// Runs `spec` on the current thread runtime, which shuts down on an
+/// unhandled panic, and on the multi thread one.
+fn on_both_runtimes<F: Future<Output = ()>>(spec: fn() -> F) {
+    run_leak_checked(false, GRACE, spec());
+    run_leak_checked(true, GRACE, spec());
+}
+
+async fn panicking_module_handles_no_more_messages() {
+    let mut system = System::new().await;
+    let (fragile, mut handled_receiver) = register_fragile(&mut system).await;
+
+    fragile.send(1).await;
+    fragile.send(Explode).await;
+    fragile.send(2).await;
+    fragile.send(3).await;
+
+    assert_eq!(handled_receiver.recv().await, Some(1));
+    /* The channel closes once the module is dropped */
+    assert_eq!(handled_receiver.recv().await, None);
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn panicking_module_handles_no_more_messages_and_is_dropped() {
+    on_both_runtimes(panicking_module_handles_no_more_messages);
+}
+
+async fn queued_messages_of_panicking_module_are_dropped() {
+    let mut system = System::new().await;
+    let (fragile, mut handled_receiver) = register_fragile(&mut system).await;
+    let drop_counter = DropCounter::new();
+
+    fragile.send(ExplodeAfter(Duration::from_millis(20))).await;
+    for _ in 0..5 {
+        fragile.send(drop_counter.message()).await;
+    }
+    assert_eq!(handled_receiver.recv().await, None);
+    /* The queue may be dropped right after the module */
+    let deadline = Instant::now() + Duration::from_millis(100);
+    while drop_counter.dropped() < 5 && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    assert_eq!(drop_counter.dropped(), 5);
+
+    fragile.send(drop_counter.message()).await;
+    assert_eq!(drop_counter.dropped(), 6);
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn queued_messages_of_panicking_module_are_dropped_before_shutdown() {
+    on_both_runtimes(queued_messages_of_panicking_module_are_dropped);
+}
+
+async fn send_to_panicked_module() {
+    let mut system = System::new().await;
+    let (fragile, mut handled_receiver) = register_fragile(&mut system).await;
+    let earlier_timer = fragile.request_tick(7, Duration::from_millis(5)).await;
+
+    fragile.send(Explode).await;
+    while handled_receiver.recv().await.is_some() {}
+
+    fragile.send(1).await;
+    fragile.clone().send(Explode).await;
+    let later_timer = fragile.request_tick(8, Duration::from_millis(5)).await;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    later_timer.stop().await;
+    earlier_timer.stop().await;
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn sending_to_panicked_module_does_not_panic() {
+    on_both_runtimes(send_to_panicked_module);
+}
+
+async fn other_modules_keep_working() {
+    let mut system = System::new().await;
+    let (peer_ref, mut spy) = Spy::<u32>::register(&mut system).await;
+    let timer = peer_ref.request_tick(0, Duration::from_millis(10)).await;
+    let (fragile, mut handled_receiver) = register_fragile(&mut system).await;
+
+    fragile.send(Explode).await;
+    assert_eq!(handled_receiver.recv().await, None);
+
+    peer_ref.send(1).await;
+    let mut received = Vec::new();
+    while received.len() < 4 {
+        received.push(spy.recv_timeout(Duration::from_millis(100)).await.unwrap());
+    }
+    assert!(received.contains(&1), "Got only {:?}", received);
+    assert!(
+        received.iter().filter(|&&num| num == 0).count() >= 3,
+        "Ticks stopped, got {:?}",
+        received
+    );
+    timer.stop().await;
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn other_modules_keep_working_after_a_panic() {
+    on_both_runtimes(other_modules_keep_working);
+}
+
+async fn shutdown_completes_while_handler_panics() {
+    let mut system = System::new().await;
+    let (fragile, _handled_receiver) = register_fragile(&mut system).await;
+    let (peer_ref, _spy) = Spy::<u32>::register(&mut system).await;
+    peer_ref.send(1).await;
+
+    fragile.send(ExplodeAfter(Duration::from_millis(30))).await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn shutdown_completes_while_a_handler_is_about_to_panic() {
+    on_both_runtimes(shutdown_completes_while_handler_panics);
+}
+
+async fn shutdown_completes_after_a_panic() {
+    let mut system = System::new().await;
+    for _ in 0..3 {
+        let (fragile, _handled_receiver) = register_fragile(&mut system).await;
+        fragile.send(Explode).await;
+    }
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(2000)]
+fn shutdown_completes_after_panics_of_several_modules() {
+    on_both_runtimes(shutdown_completes_after_a_panic);
+}
@@ -0,0 +1,409 @@
+// The leak-checked scenarios need tokio_unstable, their modules are unused
+// without it.
+#![cfg_attr(not(tokio_unstable), allow(dead_code, unused_imports))]
+
+use assignment_1_solution::{ModuleRef, System};
+use assignment_1_test_utils::adapter::{ModuleSystem, SelfRef};
+use assignment_1_test_utils::modules::{Counter, SleepyCounter, Tick};
+use assignment_1_test_utils::spy::{MessageSpy, Spy};
+use assignment_1_test_utils::tasks::verify_workers_done;
+use assignment_1_test_utils::{handler, with_self_ref};
+use ntest::timeout;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+
+// Scenarios written once for both module-system APIs: the test-utils crate
+// picks the API by its `api-2025` feature, see README.md. Time-dependent ones
+// run on tokio's paused clock.
+
+const ROUNDS: u32 = 5;
+
+struct PingPong {
+    other: Option<ModuleRef<PingPong>>,
+    received_msgs: u32,
+    first: bool,
+    name: &'static str,
+    log_sender: UnboundedSender<String>,
+}
+
+#[derive(Clone)]
+struct Ball;
+
+#[derive(Clone)]
+struct Init {
+    target: ModuleRef<PingPong>,
+}
+
+handler!(PingPong, Init, |self, msg| {
+    self.other = Some(msg.target);
+    if self.first {
+        self.other.as_ref().unwrap().send(Ball).await;
+    }
+});
+
+handler!(PingPong, Ball, |self, _msg| {
+    self.log_sender
+        .send(prepare_msg(self.name, self.received_msgs))
+        .unwrap();
+
+    self.received_msgs += 1;
+    if self.received_msgs < ROUNDS {
+        self.other.as_ref().unwrap().send(Ball).await;
+    }
+});
+
+fn prepare_msg(name: &str, round: u32) -> String {
+    format!("In {}: received {}\n", name, round)
+}
+
+#[tokio::test]
+#[timeout(300)]
+async fn ping_pong_runs_correctly() {
+    let mut system = System::new().await;
+    let (log_sender, mut log_receiver) = unbounded_channel();
+    let ping = system
+        .register(PingPong {
+            other: None,
+            name: "Ping",
+            received_msgs: 0,
+            first: true,
+            log_sender: log_sender.clone(),
+        })
+        .await;
+    let pong = system
+        .register(PingPong {
+            other: None,
+            name: "Pong",
+            received_msgs: 0,
+            first: false,
+            log_sender,
+        })
+        .await;
+
+    pong.send(Init {
+        target: ping.clone(),
+    })
+    .await;
+    ping.send(Init { target: pong }).await;
+
+    for round in 0..ROUNDS {
+        let names = if round < ROUNDS - 1 {
+            vec!["Pong", "Ping"]
+        } else {
+            vec!["Pong"]
+        };
+        for name in names {
+            assert_eq!(prepare_msg(name, round), log_receiver.recv().await.unwrap());
+        }
+    }
+    system.shutdown().await;
+}
+
+struct CountToFive {
+    self_ref: SelfRef<CountToFive>,
+    five_sender: UnboundedSender<u8>,
+}
+
+with_self_ref!(CountToFive, self_ref);
+
+handler!(CountToFive, u8, |self, self_ref, msg| {
+    if msg == 5 {
+        self.five_sender.send(msg).unwrap();
+    } else {
+        self_ref.send(msg + 1).await;
+    }
+});
+
+#[tokio::test]
+#[timeout(300)]
+async fn self_ref_works() {
+    let mut system = System::new().await;
+    let (five_sender, mut five_receiver) = unbounded_channel();
+    let count_to_five = system
+        .register_with_self_ref(CountToFive {
+            self_ref: SelfRef::default(),
+            five_sender,
+        })
+        .await;
+
+    count_to_five.send(1).await;
+
+    assert_eq!(five_receiver.recv().await.unwrap(), 5);
+    system.shutdown().await;
+}
+
+struct ComputeMessage {
+    value: u64,
+    n: u64,
+}
+
+struct SetDivModuleMessage {
+    module: ModuleRef<DivideModule>,
+}
+
+struct ResultMessage {
+    result: u64,
+}
+
+struct MultiplyModule {
+    div_mod: Option<ModuleRef<DivideModule>>,
+    res_mod: ModuleRef<MessageSpy<ResultMessage>>,
+}
+
+handler!(MultiplyModule, ComputeMessage, |self, msg| {
+    assert_eq!(msg.value % 2, 1);
+
+    if msg.value == 1 {
+        self.res_mod.send(ResultMessage { result: msg.n }).await;
+    } else {
+        self.div_mod
+            .as_ref()
+            .unwrap()
+            .send(ComputeMessage {
+                value: 3 * msg.value + 1,
+                n: msg.n + 1,
+            })
+            .await;
+    }
+});
+
+handler!(MultiplyModule, SetDivModuleMessage, |self, msg| {
+    self.div_mod = Some(msg.module);
+});
+
+struct DivideModule {
+    self_ref: SelfRef<DivideModule>,
+    mul_mod: ModuleRef<MultiplyModule>,
+}
+
+with_self_ref!(DivideModule, self_ref);
+
+handler!(DivideModule, ComputeMessage, |self, self_ref, msg| {
+    assert_eq!(msg.value % 2, 0);
+
+    let message = ComputeMessage {
+        value: msg.value / 2,
+        n: msg.n + 1,
+    };
+    if message.value.is_multiple_of(2) {
+        self_ref.send(message).await;
+    } else {
+        self.mul_mod.send(message).await;
+    }
+});
+
+/// Starts computing the number of steps of `n` to reach 1, which the spy
+/// receives.
+async fn start_collatz(system: &mut System, n: u64) -> Spy<ResultMessage> {
+    let (res_ref, res_spy) = Spy::register(system).await;
+    let mul_ref = system
+        .register(MultiplyModule {
+            div_mod: None,
+            res_mod: res_ref,
+        })
+        .await;
+    let div_ref = system
+        .register_with_self_ref(DivideModule {
+            self_ref: SelfRef::default(),
+            mul_mod: mul_ref.clone(),
+        })
+        .await;
+    mul_ref
+        .send(SetDivModuleMessage {
+            module: div_ref.clone(),
+        })
+        .await;
+
+    let init_msg = ComputeMessage { value: n, n: 1 };
+    if n.is_multiple_of(2) {
+        div_ref.send(init_msg).await;
+    } else {
+        mul_ref.send(init_msg).await;
+    }
+    res_spy
+}
+
+async fn collatz_steps(n: u64) -> u64 {
+    let mut system = System::new().await;
+    let mut res_spy = start_collatz(&mut system, n).await;
+
+    let result = res_spy.recv().await;
+    system.shutdown().await;
+    result.result
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(unhandled_panic = "shutdown_runtime")]
+#[timeout(500)]
+async fn collatz_of_one_takes_one_step() {
+    assert_eq!(collatz_steps(1).await, 1);
+    verify_workers_done();
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(unhandled_panic = "shutdown_runtime")]
+#[timeout(500)]
+async fn collatz_of_power_of_two_only_divides() {
+    assert_eq!(collatz_steps(2048).await, 12);
+    verify_workers_done();
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(unhandled_panic = "shutdown_runtime")]
+#[timeout(500)]
+async fn collatz_of_large_number_runs_correctly() {
+    assert_eq!(collatz_steps(1_234_567).await, 112);
+    verify_workers_done();
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(unhandled_panic = "shutdown_runtime")]
+#[timeout(500)]
+async fn collatz_modules_live_until_shutdown() {
+    let mut system = System::new().await;
+    let mut res_spy = start_collatz(&mut system, 1_234_567).await;
+
+    assert_eq!(res_spy.recv().await.result, 112);
+    let metrics = tokio::runtime::Handle::current().metrics();
+    assert!(metrics.num_alive_tasks() > 0);
+    system.shutdown().await;
+    verify_workers_done();
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(500)]
+async fn stopping_ticks_works() {
+    let mut system = System::new().await;
+    let (num_sender, mut num_receiver) = unbounded_channel();
+    let counter_ref = system.register(Counter { num: 0, num_sender }).await;
+
+    let timer_handle = counter_ref
+        .request_tick(Tick, Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(170)).await;
+    timer_handle.stop().await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let mut received_numbers = Vec::new();
+    while let Ok(num) = num_receiver.try_recv() {
+        received_numbers.push(num);
+    }
+    assert_eq!(received_numbers, vec![0, 1, 2]);
+    system.shutdown().await;
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(
+    flavor = "current_thread",
+    start_paused = true,
+    unhandled_panic = "shutdown_runtime"
+)]
+#[timeout(500)]
+async fn started_handlers_finish_after_shutdown() {
+    let mut system = System::new().await;
+    let (num_sender, mut num_receiver) = unbounded_channel();
+    let mut counters = Vec::new();
+    for sleep_in_millis in [50, 70] {
+        counters.push(
+            system
+                .register(SleepyCounter {
+                    num: 0,
+                    sleep_in_millis,
+                    num_sender: num_sender.clone(),
+                })
+                .await,
+        );
+    }
+    for _ in 0..5 {
+        for counter in &counters {
+            counter.send(Tick).await;
+        }
+    }
+
+    /* Both are in the middle of handling their second tick */
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    system.shutdown().await;
+
+    let mut received_numbers = Vec::new();
+    while let Ok(num) = num_receiver.try_recv() {
+        received_numbers.push(num);
+    }
+    received_numbers.sort();
+    assert_eq!(received_numbers, vec![0, 0, 1, 1]);
+    verify_workers_done();
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(
+    flavor = "current_thread",
+    start_paused = true,
+    unhandled_panic = "shutdown_runtime"
+)]
+#[timeout(500)]
+async fn tickers_stop_with_the_system() {
+    let mut system = System::new().await;
+    let (num_sender, mut num_receiver) = unbounded_channel();
+    let counter_ref = system.register(Counter { num: 0, num_sender }).await;
+
+    counter_ref
+        .request_tick(Tick, Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(70)).await;
+    system.shutdown().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(num_receiver.try_recv(), Ok(0));
+    assert!(num_receiver.try_recv().is_err());
+    verify_workers_done();
+}
+
+struct PersonPoker {
+    self_ref: SelfRef<PersonPoker>,
+}
+
+with_self_ref!(PersonPoker, self_ref);
+
+#[derive(Clone)]
+struct Poke {
+    module_ref: ModuleRef<PersonPoker>,
+}
+
+handler!(PersonPoker, Poke, |self, self_ref, msg| {
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    msg.module_ref
+        .send(Poke {
+            module_ref: self_ref.clone(),
+        })
+        .await;
+});
+
+#[cfg(tokio_unstable)]
+#[tokio::test(
+    flavor = "current_thread",
+    start_paused = true,
+    unhandled_panic = "shutdown_runtime"
+)]
+#[timeout(500)]
+async fn send_doesnt_panic_when_shutdown() {
+    let mut system = System::new().await;
+    let alice_ref = system
+        .register_with_self_ref(PersonPoker {
+            self_ref: SelfRef::default(),
+        })
+        .await;
+    let bob_ref = system
+        .register_with_self_ref(PersonPoker {
+            self_ref: SelfRef::default(),
+        })
+        .await;
+
+    alice_ref
+        .send(Poke {
+            module_ref: bob_ref,
+        })
+        .await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    /* Alice pokes Bob while the system is shutting down */
+    system.shutdown().await;
+    verify_workers_done();
+}
@@ -0,0 +1,274 @@
+//! The shutdown protocol of the module system, modelled on loom's threads and
+//! locks so that loom can check every interleaving of it. Loom cannot run
+//! tokio, so only this design is checked, not any solution: a solution passes
+//! here only as far as it follows the design the tests of the assignment
+//! assume:
+//!  - every module runs on its own task, which stops before its next message
+//!    once the system shuts down, dropping its queue,
+//!  - a timer is a task which sends ticks until it is stopped or its module
+//!    is gone, stopping it is synchronized with sending a tick,
+//!  - `System::shutdown` closes every mailbox and waits for all tasks.
+//!
+//! Timers send a fixed number of ticks, as loom has no clock. A handler which
+//! starts after `System::shutdown` returned panics, failing the model. A
+//! `Flaw` breaks the protocol on purpose, to check that the model catches it.
+
+use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use loom::sync::{Arc, Condvar, Mutex};
+use loom::thread::{self, JoinHandle};
+use std::collections::VecDeque;
+
+pub trait Module: Send + 'static {
+    type Message: Send + 'static;
+
+    fn handle(&mut self, msg: Self::Message);
+}
+
+/// Queue of a module. Closing it drops the queued messages and the ones sent
+/// later.
+struct Mailbox<M> {
+    state: Mutex<MailboxState<M>>,
+    changed: Condvar,
+}
+
+struct MailboxState<M> {
+    queue: VecDeque<M>,
+    closed: bool,
+}
+
+impl<M> Mailbox<M> {
+    fn new() -> Mailbox<M> {
+        Mailbox {
+            state: Mutex::new(MailboxState {
+                queue: VecDeque::new(),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn send(&self, msg: M) {
+        let mut state = self.state.lock().unwrap();
+        if !state.closed {
+            state.queue.push_back(msg);
+            self.changed.notify_one();
+        }
+    }
+
+    /// The next message, or `None` once the mailbox is closed, even if
+    /// messages are queued.
+    fn recv(&self) -> Option<M> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            if let Some(msg) = state.queue.pop_front() {
+                return Some(msg);
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.queue.clear();
+        self.changed.notify_all();
+    }
+}
+
+/// A deliberate bug in the protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flaw {
+    /// A timer checks that it is not stopped and sends the tick without
+    /// holding the lock in between, so a tick may follow `stop`.
+    UnlockedTimer,
+    /// `shutdown` closes the mailboxes but does not wait for the tasks, so a
+    /// handler may start after it returns.
+    UnjoinedShutdown,
+}
+
+#[derive(Default)]
+struct Shared {
+    tasks: Mutex<Tasks>,
+    flaw: Option<Flaw>,
+    shutdown_returned: AtomicBool,
+    alive_tasks: AtomicUsize,
+}
+
+#[derive(Default)]
+struct Tasks {
+    shutting_down: bool,
+    handles: Vec<JoinHandle<()>>,
+    mailbox_closers: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+/// Spawns a task of the system, unless it is shutting down.
+fn spawn(shared: &Arc<Shared>, task: impl FnOnce() + Send + 'static) {
+    let mut tasks = shared.tasks.lock().unwrap();
+    if tasks.shutting_down {
+        return;
+    }
+    shared.alive_tasks.fetch_add(1, Ordering::SeqCst);
+    let shared = shared.clone();
+    tasks.handles.push(thread::spawn(move || {
+        task();
+        shared.alive_tasks.fetch_sub(1, Ordering::SeqCst);
+    }));
+}
+
+pub struct System {
+    shared: Arc<Shared>,
+}
+
+impl System {
+    pub fn new() -> System {
+        System {
+            shared: Arc::new(Shared::default()),
+        }
+    }
+
+    /// A system whose protocol has `flaw`.
+    pub fn with_flaw(flaw: Flaw) -> System {
+        System {
+            shared: Arc::new(Shared {
+                flaw: Some(flaw),
+                ..Shared::default()
+            }),
+        }
+    }
+
+    pub fn register_module<T: Module>(&mut self, mut module: T) -> ModuleRef<T> {
+        let mailbox = Arc::new(Mailbox::new());
+        let module_ref = ModuleRef {
+            mailbox: mailbox.clone(),
+            shared: self.shared.clone(),
+        };
+
+        let shared = self.shared.clone();
+        let queue = mailbox.clone();
+        spawn(&self.shared, move || {
+            while let Some(msg) = queue.recv() {
+                assert!(
+                    !shared.shutdown_returned.load(Ordering::SeqCst),
+                    "A handler started after shutdown returned"
+                );
+                module.handle(msg);
+            }
+        });
+        self.shared
+            .tasks
+            .lock()
+            .unwrap()
+            .mailbox_closers
+            .push(Box::new(move || mailbox.close()));
+        module_ref
+    }
+
+    pub fn shutdown(&mut self) {
+        let (handles, mailbox_closers) = {
+            let mut tasks = self.shared.tasks.lock().unwrap();
+            tasks.shutting_down = true;
+            (
+                std::mem::take(&mut tasks.handles),
+                std::mem::take(&mut tasks.mailbox_closers),
+            )
+        };
+        for close in mailbox_closers {
+            close();
+        }
+        if self.shared.flaw != Some(Flaw::UnjoinedShutdown) {
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+        self.shared.shutdown_returned.store(true, Ordering::SeqCst);
+    }
+
+    /// Tasks of the system which have not finished yet.
+    pub fn alive_tasks(&self) -> usize {
+        self.shared.alive_tasks.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for System {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ModuleRef<T: Module> {
+    mailbox: Arc<Mailbox<T::Message>>,
+    shared: Arc<Shared>,
+}
+
+impl<T: Module> ModuleRef<T> {
+    pub fn send(&self, msg: T::Message) {
+        self.mailbox.send(msg);
+    }
+
+    /// Sends `msg` to the module `ticks` times from a timer task.
+    pub fn request_tick(&self, msg: T::Message, ticks: usize) -> TimerHandle
+    where
+        T::Message: Clone,
+    {
+        let timer = Arc::new(Timer {
+            stopped: Mutex::new(false),
+            fired: AtomicUsize::new(0),
+        });
+        let handle = TimerHandle {
+            timer: timer.clone(),
+        };
+        let mailbox = self.mailbox.clone();
+        let unlocked = self.shared.flaw == Some(Flaw::UnlockedTimer);
+        spawn(&self.shared, move || {
+            for _ in 0..ticks {
+                let stopped = timer.stopped.lock().unwrap();
+                if *stopped || mailbox.is_closed() {
+                    return;
+                }
+                /* Held until the tick is sent, unless flawed */
+                let _stopped = (!unlocked).then_some(stopped);
+                mailbox.send(msg.clone());
+                timer.fired.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        handle
+    }
+}
+
+impl<T: Module> Clone for ModuleRef<T> {
+    fn clone(&self) -> Self {
+        ModuleRef {
+            mailbox: self.mailbox.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+struct Timer {
+    stopped: Mutex<bool>,
+    fired: AtomicUsize,
+}
+
+#[derive(Clone)]
+pub struct TimerHandle {
+    timer: Arc<Timer>,
+}
+
+impl TimerHandle {
+    /// No tick is sent once this returns.
+    pub fn stop(&self) {
+        *self.timer.stopped.lock().unwrap() = true;
+    }
+
+    /// Ticks sent so far.
+    pub fn fired(&self) -> usize {
+        self.timer.fired.load(Ordering::SeqCst)
+    }
+}
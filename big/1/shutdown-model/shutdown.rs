@@ -0,0 +1,184 @@
+use assignment_1_shutdown_model::{Flaw, Module, ModuleRef, System};
+use loom::model::Builder;
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use loom::sync::Arc;
+use loom::thread;
+
+// Every interleaving of `System::shutdown` with sends, ticks and stopping a
+// timer, up to PREEMPTIONS preemptions. In each one nothing panics, no
+// handler starts after shutdown returns and no task is left alive. Loom
+// also fails a model which deadlocks or leaks an `Arc`. The same models run
+// on systems with a `Flaw`, which they have to catch.
+
+const PREEMPTIONS: usize = 3;
+
+fn check(model: impl Fn() + Sync + Send + 'static) {
+    let mut builder = Builder::new();
+    builder.preemption_bound = Some(PREEMPTIONS);
+    builder.check(model);
+}
+
+struct Counter {
+    handled: Arc<AtomicUsize>,
+}
+
+impl Module for Counter {
+    type Message = u32;
+
+    fn handle(&mut self, _msg: u32) {
+        self.handled.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Passes every message on to another module, from its handler.
+struct Forwarder {
+    next: ModuleRef<Counter>,
+}
+
+impl Module for Forwarder {
+    type Message = u32;
+
+    fn handle(&mut self, msg: u32) {
+        self.next.send(msg);
+    }
+}
+
+fn assert_shut_down(system: &System) {
+    assert_eq!(system.alive_tasks(), 0, "A task is alive after shutdown");
+}
+
+fn shutdown_races_send(new_system: fn() -> System) {
+    check(move || {
+        let mut system = new_system();
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = system.register_module(Counter {
+            handled: handled.clone(),
+        });
+        let forwarder = system.register_module(Forwarder {
+            next: counter.clone(),
+        });
+
+        let sender = thread::spawn(move || {
+            counter.send(1);
+            forwarder.send(2);
+        });
+        system.shutdown();
+        sender.join().unwrap();
+
+        assert_shut_down(&system);
+        assert!(handled.load(Ordering::SeqCst) <= 2);
+    });
+}
+
+fn shutdown_races_tick(new_system: fn() -> System) {
+    check(move || {
+        let mut system = new_system();
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = system.register_module(Counter {
+            handled: handled.clone(),
+        });
+
+        let timer_handle = counter.request_tick(0, 2);
+        system.shutdown();
+
+        assert_shut_down(&system);
+        assert!(handled.load(Ordering::SeqCst) <= timer_handle.fired());
+        assert!(timer_handle.fired() <= 2);
+    });
+}
+
+fn stop_races_tick_and_shutdown(new_system: fn() -> System) {
+    check(move || {
+        let mut system = new_system();
+        let counter = system.register_module(Counter {
+            handled: Arc::new(AtomicUsize::new(0)),
+        });
+
+        let timer_handle = counter.request_tick(0, 2);
+        let stopper = {
+            let timer_handle = timer_handle.clone();
+            thread::spawn(move || {
+                timer_handle.stop();
+                timer_handle.fired()
+            })
+        };
+        system.shutdown();
+        let fired_at_stop = stopper.join().unwrap();
+
+        assert_shut_down(&system);
+        assert_eq!(
+            timer_handle.fired(),
+            fired_at_stop,
+            "A tick was sent after stop returned"
+        );
+    });
+}
+
+fn send_and_tick_after_shutdown(new_system: fn() -> System) {
+    check(move || {
+        let mut system = new_system();
+        let handled = Arc::new(AtomicUsize::new(0));
+        let counter = system.register_module(Counter {
+            handled: handled.clone(),
+        });
+
+        system.shutdown();
+        let sender = thread::spawn(move || {
+            counter.send(1);
+            let timer_handle = counter.request_tick(0, 2);
+            timer_handle.stop();
+            timer_handle.fired()
+        });
+
+        assert_eq!(sender.join().unwrap(), 0);
+        assert_shut_down(&system);
+        assert_eq!(handled.load(Ordering::SeqCst), 0);
+    });
+}
+
+#[test]
+fn shutdown_races_send_safely() {
+    shutdown_races_send(System::new);
+}
+
+#[test]
+fn shutdown_races_tick_safely() {
+    shutdown_races_tick(System::new);
+}
+
+#[test]
+fn stop_races_tick_and_shutdown_safely() {
+    stop_races_tick_and_shutdown(System::new);
+}
+
+#[test]
+fn send_and_tick_after_shutdown_do_nothing() {
+    send_and_tick_after_shutdown(System::new);
+}
+
+#[test]
+#[should_panic(expected = "A tick was sent after stop returned")]
+fn unlocked_timer_is_caught() {
+    stop_races_tick_and_shutdown(|| System::with_flaw(Flaw::UnlockedTimer));
+}
+
+#[test]
+#[should_panic(expected = "A task is alive after shutdown")]
+fn unjoined_shutdown_is_caught() {
+    shutdown_races_send(|| System::with_flaw(Flaw::UnjoinedShutdown));
+}
+
+#[test]
+#[should_panic(expected = "A handler started after shutdown returned")]
+fn handler_started_after_unjoined_shutdown_is_caught() {
+    check(|| {
+        let mut system = System::with_flaw(Flaw::UnjoinedShutdown);
+        let counter = system.register_module(Counter {
+            handled: Arc::new(AtomicUsize::new(0)),
+        });
+
+        counter.send(1);
+        system.shutdown();
+        /* No check of the tasks, the late handler has to fail the model */
+    });
+}
@@ -0,0 +1,92 @@
+#![cfg(tokio_unstable)]
+
+use assignment_1_solution::System;
+use assignment_1_test_utils::adapter::ModuleSystem;
+use assignment_1_test_utils::leaks::{leak_checked_runtime, run_leak_checked, LeakReport};
+use assignment_1_test_utils::modules::{SleepyCounter, Tick};
+use ntest::timeout;
+use std::time::Duration;
+use tokio::sync::mpsc::unbounded_channel;
+
+// The tests need tokio_unstable, see README.md, as task hooks are unstable.
+
+async fn tick_and_shut_down() {
+    let mut system = System::new().await;
+    let (num_sender, _num_receiver) = unbounded_channel();
+    for i in 0..4 {
+        let counter = system
+            .register(SleepyCounter {
+                num: 0,
+                sleep_in_millis: 50,
+                num_sender: num_sender.clone(),
+            })
+            .await;
+        if i % 2 == 0 {
+            for interval in [10, 20, 30] {
+                counter
+                    .request_tick(Tick, Duration::from_millis(interval))
+                    .await;
+            }
+        } else {
+            for _ in 0..3 {
+                counter.send(Tick).await;
+            }
+        }
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    system.shutdown().await;
+}
+
+#[test]
+#[timeout(1000)]
+fn no_tasks_leak_after_shutdown_on_current_thread() {
+    run_leak_checked(false, Duration::from_millis(200), tick_and_shut_down());
+}
+
+#[test]
+#[timeout(1000)]
+fn no_tasks_leak_after_shutdown_on_multi_thread() {
+    run_leak_checked(true, Duration::from_millis(200), tick_and_shut_down());
+}
+
+#[test]
+#[timeout(1000)]
+fn leaked_task_is_reported_with_its_spawn_location() {
+    let (runtime, tracker) = leak_checked_runtime(false);
+    runtime.block_on(async {
+        let guard = tracker.guard();
+
+        let line = line!() + 1;
+        let leaked = tokio::spawn(std::future::pending::<()>());
+        tokio::spawn(async {}).await.unwrap();
+
+        let leaked_tasks = guard.leaked_tasks();
+        assert_eq!(leaked_tasks.len(), 1);
+        assert_eq!(leaked_tasks[0].id, leaked.id());
+        assert_eq!(leaked_tasks[0].spawned_at.file(), file!());
+        assert_eq!(leaked_tasks[0].spawned_at.line(), line);
+        let report = LeakReport(leaked_tasks).to_string();
+        assert!(
+            report.contains(&format!("1 spawned at {}:{}:", file!(), line)),
+            "{}",
+            report
+        );
+
+        leaked.abort();
+        guard.assert_no_leaks(Duration::from_millis(100)).await;
+    });
+}
+
+#[test]
+#[timeout(1000)]
+fn tasks_spawned_before_the_guard_are_not_leaks() {
+    let (runtime, tracker) = leak_checked_runtime(false);
+    runtime.block_on(async {
+        let earlier = tokio::spawn(std::future::pending::<()>());
+        let guard = tracker.guard();
+
+        guard.assert_no_leaks(Duration::ZERO).await;
+        assert_eq!(tracker.alive_tasks().len(), 1);
+        earlier.abort();
+    });
+}
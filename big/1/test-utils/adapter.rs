@@ -0,0 +1,64 @@
+use assignment_1_solution::{Module, ModuleRef, System};
+
+/// Registration of modules, the same in both APIs. Together with `handler!`
+/// it lets a test be written once and built against either, depending on the
+/// `api-2025` feature of this crate.
+#[async_trait::async_trait]
+pub trait ModuleSystem {
+    /// Registers a module whose handlers do not need its own reference.
+    async fn register<T: Module>(&mut self, module: T) -> ModuleRef<T>;
+
+    /// Registers a module whose handlers take `self_ref` in `handler!`.
+    async fn register_with_self_ref<T: WithSelfRef>(&mut self, module: T) -> ModuleRef<T>;
+}
+
+#[async_trait::async_trait]
+impl ModuleSystem for System {
+    #[cfg(not(feature = "api-2025"))]
+    async fn register<T: Module>(&mut self, module: T) -> ModuleRef<T> {
+        self.register_module(module).await
+    }
+
+    #[cfg(feature = "api-2025")]
+    async fn register<T: Module>(&mut self, module: T) -> ModuleRef<T> {
+        self.register_module(|_| module).await
+    }
+
+    #[cfg(not(feature = "api-2025"))]
+    async fn register_with_self_ref<T: WithSelfRef>(&mut self, module: T) -> ModuleRef<T> {
+        /* `handle` gets the reference, the slot is not needed */
+        self.register_module(module).await
+    }
+
+    #[cfg(feature = "api-2025")]
+    async fn register_with_self_ref<T: WithSelfRef>(&mut self, mut module: T) -> ModuleRef<T> {
+        self.register_module(|self_ref| {
+            module.self_ref_slot().0 = Some(self_ref);
+            module
+        })
+        .await
+    }
+}
+
+/// The reference of a module to itself, for the 2025 API, where `handle`
+/// does not get one. Create it empty, `register_with_self_ref` fills it.
+pub struct SelfRef<T: Module>(Option<ModuleRef<T>>);
+
+impl<T: Module> SelfRef<T> {
+    pub fn get(&self) -> &ModuleRef<T> {
+        self.0
+            .as_ref()
+            .expect("Module with `self_ref` was not registered with `register_with_self_ref`")
+    }
+}
+
+impl<T: Module> Default for SelfRef<T> {
+    fn default() -> Self {
+        SelfRef(None)
+    }
+}
+
+/// A module which keeps a `SelfRef`, implement it with `with_self_ref!`.
+pub trait WithSelfRef: Module + Sized {
+    fn self_ref_slot(&mut self) -> &mut SelfRef<Self>;
+}
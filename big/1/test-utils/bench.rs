@@ -0,0 +1,326 @@
+use crate::adapter::ModuleSystem;
+use assignment_1_solution::{ModuleRef, System};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+/// Result of one benchmark, printed as a single line of JSON:
+///
+/// ```text
+/// {"benchmark":"flood","modules":10000,"messages":1000000,"elapsed_ms":812.4,"per_sec":1230921.2}
+/// ```
+///
+/// Parameters come first, then the measurements. Latencies are in
+/// microseconds.
+pub struct BenchResult {
+    pub benchmark: &'static str,
+    pub params: Vec<(&'static str, u64)>,
+    pub elapsed: Duration,
+    /// Operations done in `elapsed`, reported as `per_sec`.
+    pub operations: u64,
+    pub latencies: Option<Latencies>,
+    pub extra: Vec<(&'static str, f64)>,
+}
+
+impl fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\"benchmark\":\"{}\"", self.benchmark)?;
+        for (name, value) in &self.params {
+            write!(f, ",\"{}\":{}", name, value)?;
+        }
+        let elapsed_secs = self.elapsed.as_secs_f64();
+        write!(f, ",\"elapsed_ms\":{:.1}", elapsed_secs * 1000.0)?;
+        write!(
+            f,
+            ",\"per_sec\":{:.1}",
+            self.operations as f64 / elapsed_secs.max(f64::MIN_POSITIVE)
+        )?;
+        if let Some(latencies) = &self.latencies {
+            write!(
+                f,
+                ",\"p50_us\":{:.1},\"p90_us\":{:.1},\"p99_us\":{:.1},\"max_us\":{:.1}",
+                micros(latencies.p50),
+                micros(latencies.p90),
+                micros(latencies.p99),
+                micros(latencies.max)
+            )?;
+        }
+        for (name, value) in &self.extra {
+            write!(f, ",\"{}\":{:.3}", name, value)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
+
+pub struct Latencies {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Latencies {
+    pub fn of(mut samples: Vec<Duration>) -> Option<Latencies> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+        Some(Latencies {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+/// Registers `modules` modules which do nothing.
+pub async fn register(modules: u64) -> BenchResult {
+    let mut system = System::new().await;
+    let start = Instant::now();
+    for _ in 0..modules {
+        system.register(Sink).await;
+    }
+    let elapsed = start.elapsed();
+    system.shutdown().await;
+
+    BenchResult {
+        benchmark: "register",
+        params: vec![("modules", modules)],
+        elapsed,
+        operations: modules,
+        latencies: None,
+        extra: Vec::new(),
+    }
+}
+
+struct Sink;
+
+handler!(Sink, (), |self, _msg| {});
+
+/// Sends `messages` messages from `senders` tasks, round robin over
+/// `modules` modules, and measures until the last one is handled. Needs at
+/// least one of each, or it would wait forever.
+pub async fn flood(modules: u64, messages: u64, senders: u64) -> BenchResult {
+    assert!(
+        modules > 0 && messages > 0 && senders > 0,
+        "flood needs at least one module, message and sender"
+    );
+    let mut system = System::new().await;
+    let handled = Arc::new(AtomicU64::new(0));
+    let all_handled = Arc::new(Notify::new());
+    let mut module_refs = Vec::new();
+    for _ in 0..modules {
+        module_refs.push(
+            system
+                .register(Counting {
+                    handled: handled.clone(),
+                    target: messages,
+                    all_handled: all_handled.clone(),
+                })
+                .await,
+        );
+    }
+    let module_refs = Arc::new(module_refs);
+
+    let start = Instant::now();
+    for sender_idx in 0..senders {
+        let module_refs = module_refs.clone();
+        tokio::spawn(async move {
+            let mut msg_idx = sender_idx;
+            while msg_idx < messages {
+                module_refs[(msg_idx % modules) as usize].send(Ping).await;
+                msg_idx += senders;
+            }
+        });
+    }
+    /* `notify_one` keeps the permit if we are not waiting yet */
+    all_handled.notified().await;
+    let elapsed = start.elapsed();
+    system.shutdown().await;
+
+    BenchResult {
+        benchmark: "flood",
+        params: vec![
+            ("modules", modules),
+            ("messages", messages),
+            ("senders", senders),
+        ],
+        elapsed,
+        operations: messages,
+        latencies: None,
+        extra: Vec::new(),
+    }
+}
+
+#[derive(Clone)]
+struct Ping;
+
+struct Counting {
+    handled: Arc<AtomicU64>,
+    target: u64,
+    all_handled: Arc<Notify>,
+}
+
+handler!(Counting, Ping, |self, _msg| {
+    if self.handled.fetch_add(1, Ordering::Relaxed) + 1 == self.target {
+        self.all_handled.notify_one();
+    }
+});
+
+/// Sends a message around a ring of `ring` modules `round_trips` times, one
+/// round trip at a time, and measures the latency of each.
+pub async fn ping_pong(ring: u64, round_trips: u64) -> BenchResult {
+    let mut system = System::new().await;
+    let (done_sender, mut done_receiver) = unbounded_channel();
+    let mut links = Vec::new();
+    for _ in 0..ring {
+        links.push(
+            system
+                .register(Link {
+                    next: None,
+                    done_sender: done_sender.clone(),
+                })
+                .await,
+        );
+    }
+    for (link_idx, link) in links.iter().enumerate() {
+        link.send(SetNext(links[(link_idx + 1) % links.len()].clone()))
+            .await;
+    }
+
+    let mut latencies = Vec::new();
+    let start = Instant::now();
+    for _ in 0..round_trips {
+        let sent_at = Instant::now();
+        links[0].send(Hop(ring)).await;
+        done_receiver.recv().await.unwrap();
+        latencies.push(sent_at.elapsed());
+    }
+    let elapsed = start.elapsed();
+    system.shutdown().await;
+
+    BenchResult {
+        benchmark: "ping_pong",
+        params: vec![("ring", ring), ("round_trips", round_trips)],
+        elapsed,
+        operations: round_trips,
+        latencies: Latencies::of(latencies),
+        extra: Vec::new(),
+    }
+}
+
+struct Link {
+    next: Option<ModuleRef<Link>>,
+    done_sender: UnboundedSender<()>,
+}
+
+struct SetNext(ModuleRef<Link>);
+
+/// A message with the number of hops left to make.
+struct Hop(u64);
+
+handler!(Link, SetNext, |self, msg| {
+    self.next = Some(msg.0);
+});
+
+handler!(Link, Hop, |self, msg| {
+    if msg.0 == 0 {
+        self.done_sender.send(()).unwrap();
+    } else {
+        self.next.as_ref().unwrap().send(Hop(msg.0 - 1)).await;
+    }
+});
+
+/// Runs `timers` timers with the given `interval`, spread over `modules`
+/// modules, for `duration`. Measures how late the ticks are handled and how
+/// many of the expected ticks arrived.
+pub async fn timers(
+    modules: u64,
+    timers: u64,
+    interval: Duration,
+    duration: Duration,
+) -> BenchResult {
+    let mut system = System::new().await;
+    let lateness = Arc::new(Mutex::new(Vec::new()));
+    let mut module_refs = Vec::new();
+    for _ in 0..modules {
+        module_refs.push(
+            system
+                .register(TickCounting {
+                    ticks: HashMap::new(),
+                    lateness: lateness.clone(),
+                })
+                .await,
+        );
+    }
+
+    let start = Instant::now();
+    let mut handles = Vec::new();
+    for timer in 0..timers {
+        let tick = TimerTick {
+            timer,
+            requested_at: Instant::now(),
+            interval,
+        };
+        handles.push(
+            module_refs[(timer % modules) as usize]
+                .request_tick(tick, interval)
+                .await,
+        );
+    }
+    tokio::time::sleep_until(start + duration).await;
+    for handle in &handles {
+        handle.stop().await;
+    }
+    let elapsed = start.elapsed();
+    system.shutdown().await;
+
+    let lateness = std::mem::take(&mut *lateness.lock().unwrap());
+    let delivered = lateness.len() as u64;
+    /* Timers requested later tick less often, by at most one */
+    let expected = timers * (duration.as_nanos() / interval.as_nanos()) as u64;
+    BenchResult {
+        benchmark: "timers",
+        params: vec![
+            ("modules", modules),
+            ("timers", timers),
+            ("interval_ms", interval.as_millis() as u64),
+        ],
+        elapsed,
+        operations: delivered,
+        latencies: Latencies::of(lateness),
+        extra: vec![("delivered_ratio", delivered as f64 / expected.max(1) as f64)],
+    }
+}
+
+#[derive(Clone)]
+struct TimerTick {
+    timer: u64,
+    requested_at: Instant,
+    interval: Duration,
+}
+
+struct TickCounting {
+    ticks: HashMap<u64, u32>,
+    lateness: Arc<Mutex<Vec<Duration>>>,
+}
+
+handler!(TickCounting, TimerTick, |self, msg| {
+    let ticks = self.ticks.entry(msg.timer).or_default();
+    *ticks += 1;
+    let due = msg.requested_at + msg.interval * *ticks;
+    self.lateness
+        .lock()
+        .unwrap()
+        .push(Instant::now().saturating_duration_since(due));
+});
@@ -0,0 +1,97 @@
+//! Throughput and latency of the module system, one line of JSON per
+//! benchmark on standard output.
+//!
+//! `cargo run --release --bin bench > baseline.jsonl` runs all benchmarks at
+//! full size; `--scale 10` divides every size by ten, but not below one, for
+//! a quick run. Compare the `per_sec` and latency fields of two such files to
+//! tell whether a change of `System` slowed it down.
+use assignment_1_test_utils::bench::{flood, ping_pong, register, timers};
+use std::time::Duration;
+
+const USAGE: &str = "Usage: bench [--scale N] [--only register|flood|ping_pong|timers] \
+    [--threads N]";
+
+const BENCHMARKS: [&str; 4] = ["register", "flood", "ping_pong", "timers"];
+
+struct Args {
+    scale: u64,
+    only: Option<String>,
+    threads: usize,
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+    let runtime = if args.threads == 1 {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(args.threads)
+            .enable_all()
+            .build()
+    }
+    .unwrap();
+
+    let scale = args.scale;
+    for benchmark in BENCHMARKS {
+        if args.only.as_deref().is_some_and(|only| only != benchmark) {
+            continue;
+        }
+        let result = runtime.block_on(async {
+            match benchmark {
+                "register" => register(scaled(10_000, scale)).await,
+                "flood" => flood(scaled(10_000, scale), scaled(2_000_000, scale), 4).await,
+                "ping_pong" => ping_pong(2, scaled(100_000, scale)).await,
+                "timers" => {
+                    timers(
+                        scaled(100, scale),
+                        scaled(5_000, scale),
+                        Duration::from_millis(10),
+                        Duration::from_secs(2),
+                    )
+                    .await
+                }
+                _ => unreachable!(),
+            }
+        });
+        println!("{}", result);
+    }
+}
+
+/// Divides `size` by `scale`, but never below one: a benchmark of nothing
+/// divides by zero or waits forever.
+fn scaled(size: u64, scale: u64) -> u64 {
+    (size / scale).max(1)
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        scale: 1,
+        only: None,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of {}", arg))?;
+        match arg.as_str() {
+            "--scale" => parsed.scale = parse_number(&value)?.max(1),
+            "--threads" => parsed.threads = parse_number(&value)?.max(1) as usize,
+            "--only" if BENCHMARKS.contains(&value.as_str()) => parsed.only = Some(value),
+            _ => return Err(format!("Unknown argument: {} {}", arg, value)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn parse_number(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Not a number: {}", value))
+}
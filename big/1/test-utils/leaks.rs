@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime, UnhandledPanic};
+use tokio::task::Id;
+
+/// Keeps every alive task of a runtime with the place it was spawned at.
+/// Install it on a runtime with `TaskTracker::install` or use
+/// `leak_checked_runtime`.
+#[derive(Clone, Default)]
+pub struct TaskTracker {
+    alive: Arc<Mutex<HashMap<Id, &'static Location<'static>>>>,
+}
+
+/// A task which has not terminated yet.
+#[derive(Clone, Debug)]
+pub struct AliveTask {
+    pub id: Id,
+    pub spawned_at: &'static Location<'static>,
+}
+
+impl TaskTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn install<'a>(&self, builder: &'a mut Builder) -> &'a mut Builder {
+        let alive = self.alive.clone();
+        let terminated = self.alive.clone();
+        builder
+            .on_task_spawn(move |meta| {
+                alive.lock().unwrap().insert(meta.id(), meta.spawned_at());
+            })
+            .on_task_terminate(move |meta| {
+                terminated.lock().unwrap().remove(&meta.id());
+            })
+    }
+
+    /// Tasks alive now, ordered by where they were spawned.
+    pub fn alive_tasks(&self) -> Vec<AliveTask> {
+        let mut tasks: Vec<AliveTask> = self
+            .alive
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, &spawned_at)| AliveTask { id, spawned_at })
+            .collect();
+        tasks.sort_by_key(|task| {
+            (
+                task.spawned_at.file(),
+                task.spawned_at.line(),
+                task.spawned_at.column(),
+            )
+        });
+        tasks
+    }
+
+    /// Scopes leak checks to the tasks spawned from now on.
+    pub fn guard(&self) -> LeakGuard {
+        LeakGuard {
+            tracker: self.clone(),
+            ignored: self.alive.lock().unwrap().keys().copied().collect(),
+        }
+    }
+}
+
+/// Checks that the tasks spawned since the guard was made have terminated.
+pub struct LeakGuard {
+    tracker: TaskTracker,
+    ignored: Vec<Id>,
+}
+
+impl LeakGuard {
+    /// Tasks spawned since the guard was made which are still alive.
+    pub fn leaked_tasks(&self) -> Vec<AliveTask> {
+        self.tracker
+            .alive_tasks()
+            .into_iter()
+            .filter(|task| !self.ignored.contains(&task.id))
+            .collect()
+    }
+
+    /// Waits up to `grace` for the tasks to terminate, as aborted tasks do
+    /// so only when the runtime gets to them, and panics with a report of
+    /// the ones which did not.
+    pub async fn assert_no_leaks(&self, grace: Duration) {
+        let deadline = tokio::time::Instant::now() + grace;
+        loop {
+            let leaked = self.leaked_tasks();
+            if leaked.is_empty() {
+                return;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                panic!("{}", LeakReport(leaked));
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+}
+
+/// Leaked tasks grouped by where they were spawned.
+pub struct LeakReport(pub Vec<AliveTask>);
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut by_location: BTreeMap<(&str, u32, u32), Vec<Id>> = BTreeMap::new();
+        for task in &self.0 {
+            let location = task.spawned_at;
+            by_location
+                .entry((location.file(), location.line(), location.column()))
+                .or_default()
+                .push(task.id);
+        }
+        write!(f, "{} task(s) still alive:", self.0.len())?;
+        for ((file, line, column), ids) in by_location {
+            let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+            write!(
+                f,
+                "\n  {} spawned at {}:{}:{} (ids {})",
+                ids.len(),
+                file,
+                line,
+                column,
+                ids.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A runtime of the given flavor, with all drivers enabled, which tracks its
+/// tasks. A current thread one also shuts down on an unhandled panic, which
+/// tokio does not support for the multi thread one.
+pub fn leak_checked_runtime(multi_thread: bool) -> (Runtime, TaskTracker) {
+    let tracker = TaskTracker::new();
+    let mut builder = if multi_thread {
+        Builder::new_multi_thread()
+    } else {
+        let mut builder = Builder::new_current_thread();
+        builder.unhandled_panic(UnhandledPanic::ShutdownRuntime);
+        builder
+    };
+    tracker.install(&mut builder).enable_all();
+    (builder.build().unwrap(), tracker)
+}
+
+/// Runs `test` on a `leak_checked_runtime` and then asserts that every task
+/// it spawned has terminated within `grace`. The test is expected to shut
+/// its systems down.
+pub fn run_leak_checked<F>(multi_thread: bool, grace: Duration, test: F) -> F::Output
+where
+    F: Future,
+{
+    let (runtime, tracker) = leak_checked_runtime(multi_thread);
+    runtime.block_on(async {
+        let guard = tracker.guard();
+        let output = test.await;
+        guard.assert_no_leaks(grace).await;
+        output
+    })
+}
@@ -0,0 +1,83 @@
+#[doc(hidden)]
+pub mod __private {
+    pub use assignment_1_solution;
+    pub use async_trait::async_trait;
+}
+
+/// Implements `Handler<$msg>` for `$module` with the signature of the API the
+/// crate is built for, so that one test source compiles against both:
+///
+/// ```ignore
+/// handler!(Counter, Tick, |self, _msg| { ... });
+/// handler!(CountToFive, u8, |self, self_ref, msg| { ... });
+/// handler!([M: Send + 'static] Recorder<M>, M, |self, msg| { ... });
+/// ```
+///
+/// With `self_ref` the module must implement `WithSelfRef` and be registered
+/// with `ModuleSystem::register_with_self_ref`.
+#[cfg(not(feature = "api-2025"))]
+#[macro_export]
+macro_rules! handler {
+    ([$($generics:tt)*] $module:ty, $msg:ty, |$self:ident, $self_ref:ident, $m:pat_param| $body:block) => {
+        #[$crate::__private::async_trait]
+        impl<$($generics)*> $crate::__private::assignment_1_solution::Handler<$msg> for $module {
+            async fn handle(
+                &mut $self,
+                $self_ref: &$crate::__private::assignment_1_solution::ModuleRef<Self>,
+                $m: $msg,
+            ) $body
+        }
+    };
+    ([$($generics:tt)*] $module:ty, $msg:ty, |$self:ident, $m:pat_param| $body:block) => {
+        $crate::handler!([$($generics)*] $module, $msg, |$self, _self_ref, $m| $body);
+    };
+    ($module:ty, $($rest:tt)*) => {
+        $crate::handler!([] $module, $($rest)*);
+    };
+}
+
+#[cfg(feature = "api-2025")]
+#[macro_export]
+macro_rules! handler {
+    ([$($generics:tt)*] $module:ty, $msg:ty, |$self:ident, $self_ref:ident, $m:pat_param| $body:block) => {
+        #[$crate::__private::async_trait]
+        impl<$($generics)*> $crate::__private::assignment_1_solution::Handler<$msg> for $module {
+            async fn handle(&mut $self, $m: $msg) {
+                let $self_ref =
+                    &$crate::adapter::WithSelfRef::self_ref_slot($self).get().clone();
+                $body
+            }
+        }
+    };
+    ([$($generics:tt)*] $module:ty, $msg:ty, |$self:ident, $m:pat_param| $body:block) => {
+        #[$crate::__private::async_trait]
+        impl<$($generics)*> $crate::__private::assignment_1_solution::Handler<$msg> for $module {
+            async fn handle(&mut $self, $m: $msg) $body
+        }
+    };
+    ($module:ty, $($rest:tt)*) => {
+        $crate::handler!([] $module, $($rest)*);
+    };
+}
+
+/// Implements `WithSelfRef` for `$module`, which keeps the slot in `$field`.
+#[macro_export]
+macro_rules! with_self_ref {
+    ($module:ty, $field:ident) => {
+        impl $crate::adapter::WithSelfRef for $module {
+            fn self_ref_slot(&mut self) -> &mut $crate::adapter::SelfRef<Self> {
+                &mut self.$field
+            }
+        }
+    };
+}
+
+pub mod adapter;
+pub mod system;
+pub mod modules;
+pub mod spy;
+pub mod tasks;
+pub mod bench;
+pub mod queues;
+#[cfg(tokio_unstable)]
+pub mod leaks;
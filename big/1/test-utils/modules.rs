@@ -0,0 +1,201 @@
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Clone)]
+pub struct Tick;
+
+/// Sends consecutive numbers, starting from `num`, on every tick.
+pub struct Counter {
+    pub num: u8,
+    pub num_sender: UnboundedSender<u8>,
+}
+
+handler!(Counter, Tick, |self, _msg| {
+    self.num_sender.send(self.num).unwrap();
+    self.num += 1;
+});
+
+/// `Counter` which sleeps before sending the number, to keep a handler
+/// running when the system shuts down.
+pub struct SleepyCounter {
+    pub num: u8,
+    pub sleep_in_millis: u64,
+    pub num_sender: UnboundedSender<u8>,
+}
+
+handler!(SleepyCounter, Tick, |self, _msg| {
+    tokio::time::sleep(Duration::from_millis(self.sleep_in_millis)).await;
+    self.num_sender.send(self.num).unwrap();
+    self.num += 1;
+});
+
+#[derive(Clone)]
+pub struct SleepTask {
+    pub time: u64,
+}
+
+/// Sleeps for the time indicated by the message, then counts it.
+#[derive(Clone)]
+pub struct SleepModule {
+    pub times_slept: Arc<AtomicU64>,
+}
+
+impl SleepModule {
+    pub fn new() -> SleepModule {
+        SleepModule {
+            times_slept: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn times_slept(&self) -> u64 {
+        self.times_slept.load(Ordering::SeqCst)
+    }
+}
+
+impl Default for SleepModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+handler!(SleepModule, SleepTask, |self, msg| {
+    tokio::time::sleep(Duration::from_millis(msg.time)).await;
+
+    self.times_slept.fetch_add(1, Ordering::SeqCst);
+});
+
+/// Tick of a `ClockModule`, `time` is the interval of its timer in
+/// milliseconds.
+#[derive(Clone)]
+pub struct TimeTick {
+    pub time: u64,
+}
+
+/// Counts ticks and measures the largest drift from their expected interval,
+/// in wall-clock time.
+#[derive(Clone)]
+pub struct ClockModule {
+    pub last_sys_time: SystemTime,
+    pub max_drift: Arc<AtomicU64>,
+    pub fire_counter: Arc<AtomicU64>,
+}
+
+impl ClockModule {
+    pub fn new() -> ClockModule {
+        ClockModule {
+            last_sys_time: SystemTime::now(),
+            max_drift: Arc::new(AtomicU64::new(0)),
+            fire_counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Default for ClockModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+handler!(ClockModule, TimeTick, |self, msg| {
+    let current_time = SystemTime::now();
+    let time_diff = current_time.duration_since(self.last_sys_time).unwrap();
+
+    let expected_time = msg.time;
+    let real_time = time_diff.as_millis() as u64;
+
+    let drift = max(expected_time, real_time) - min(expected_time, real_time);
+    self.max_drift.fetch_max(drift, Ordering::SeqCst);
+    self.fire_counter.fetch_add(1, Ordering::SeqCst);
+    self.last_sys_time = current_time;
+});
+
+/// Appends every message it handles to a `Recording`, which stays readable
+/// after the system is shut down.
+pub struct Recorder<M> {
+    recording: Recording<M>,
+}
+
+impl<M> Recorder<M> {
+    pub fn new() -> (Recorder<M>, Recording<M>) {
+        let recording = Recording {
+            messages: Arc::new(Mutex::new(Vec::new())),
+        };
+        (
+            Recorder {
+                recording: recording.clone(),
+            },
+            recording,
+        )
+    }
+}
+
+handler!([M: Send + 'static] Recorder<M>, M, |self, msg| {
+    self.recording.messages.lock().unwrap().push(msg);
+});
+
+pub struct Recording<M> {
+    messages: Arc<Mutex<Vec<M>>>,
+}
+
+impl<M> Recording<M> {
+    pub fn len(&self) -> usize {
+        self.messages.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Messages handled so far, in the order of handling.
+    pub fn messages(&self) -> Vec<M>
+    where
+        M: Clone,
+    {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+impl<M> Clone for Recording<M> {
+    fn clone(&self) -> Self {
+        Recording {
+            messages: self.messages.clone(),
+        }
+    }
+}
+
+/// Hands out `Counted` messages and counts how many of them were dropped,
+/// to check that a system frees the messages it does not handle.
+#[derive(Clone, Default)]
+pub struct DropCounter {
+    dropped: Arc<AtomicU64>,
+}
+
+impl DropCounter {
+    pub fn new() -> DropCounter {
+        DropCounter::default()
+    }
+
+    pub fn message(&self) -> Counted {
+        Counted {
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+}
+
+/// Message which counts its drop in the `DropCounter` it came from.
+pub struct Counted {
+    dropped: Arc<AtomicU64>,
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+}
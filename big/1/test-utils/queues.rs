@@ -0,0 +1,197 @@
+use crate::modules::Counted;
+use assignment_1_solution::{Handler, Message, Module, ModuleRef};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// Counts the messages sent to a module through `GaugedRef`s and the ones
+/// the module reported as handled; the difference is the length of its
+/// queue, including the message being handled.
+#[derive(Clone, Default)]
+pub struct QueueGauge {
+    sent: Arc<AtomicU64>,
+    handled: Arc<AtomicU64>,
+}
+
+impl QueueGauge {
+    pub fn new() -> QueueGauge {
+        QueueGauge::default()
+    }
+
+    /// To be called by the module when it is done with a message.
+    pub fn mark_handled(&self) {
+        self.handled.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn sent(&self) -> u64 {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    pub fn handled(&self) -> u64 {
+        self.handled.load(Ordering::SeqCst)
+    }
+
+    pub fn len(&self) -> u64 {
+        /* Read `handled` first, so that it never exceeds `sent` */
+        let handled = self.handled();
+        self.sent().saturating_sub(handled)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `ModuleRef` which counts the messages sent through it in a `QueueGauge`.
+pub struct GaugedRef<T: Module> {
+    pub module_ref: ModuleRef<T>,
+    pub gauge: QueueGauge,
+}
+
+impl<T: Module> GaugedRef<T> {
+    pub async fn send<M: Message>(&self, msg: M)
+    where
+        T: Handler<M>,
+    {
+        self.gauge.sent.fetch_add(1, Ordering::SeqCst);
+        self.module_ref.send(msg).await;
+    }
+}
+
+impl<T: Module> Clone for GaugedRef<T> {
+    fn clone(&self) -> Self {
+        GaugedRef {
+            module_ref: self.module_ref.clone(),
+            gauge: self.gauge.clone(),
+        }
+    }
+}
+
+/// Message with a payload of the given size, which counts its drop.
+pub struct Parcel {
+    pub payload: Vec<u8>,
+    pub counted: Counted,
+}
+
+/// Takes `handle_time` to handle a parcel, like `SleepModule`, and reports
+/// it to its gauge.
+pub struct SlowSink {
+    pub handle_time: Duration,
+    pub gauge: QueueGauge,
+}
+
+handler!(SlowSink, Parcel, |self, _msg| {
+    tokio::time::sleep(self.handle_time).await;
+    self.gauge.mark_handled();
+});
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueueSample {
+    pub at: Duration,
+    pub queue_len: u64,
+    pub rss_bytes: u64,
+}
+
+impl QueueSample {
+    pub fn take(start: Instant, gauge: &QueueGauge) -> Self {
+        QueueSample {
+            at: start.elapsed(),
+            queue_len: gauge.len(),
+            rss_bytes: rss_bytes(),
+        }
+    }
+}
+
+/// Resident memory of the whole test binary, so it includes the tests which
+/// run in parallel.
+pub fn rss_bytes() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let kib: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .expect("No VmRSS in /proc/self/status")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .unwrap();
+    kib * 1024
+}
+
+/// Samples the queue length of a module and the memory of the process in the
+/// background until dropped.
+pub struct QueueMonitor {
+    start: Instant,
+    gauge: QueueGauge,
+    samples: Arc<Mutex<Vec<QueueSample>>>,
+    task: JoinHandle<()>,
+}
+
+impl QueueMonitor {
+    /// Call before the flood, the first sample is the baseline.
+    pub fn start(gauge: QueueGauge, interval: Duration) -> Self {
+        let start = Instant::now();
+        let samples = Arc::new(Mutex::new(vec![QueueSample::take(start, &gauge)]));
+        let task = {
+            let gauge = gauge.clone();
+            let samples = samples.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    samples
+                        .lock()
+                        .unwrap()
+                        .push(QueueSample::take(start, &gauge));
+                }
+            })
+        };
+        QueueMonitor {
+            start,
+            gauge,
+            samples,
+            task,
+        }
+    }
+
+    pub fn sample_now(&self) -> QueueSample {
+        let sample = QueueSample::take(self.start, &self.gauge);
+        self.samples.lock().unwrap().push(sample);
+        sample
+    }
+
+    pub fn baseline(&self) -> QueueSample {
+        self.samples.lock().unwrap()[0]
+    }
+
+    pub fn samples(&self) -> Vec<QueueSample> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    pub fn peak_queue_len(&self) -> u64 {
+        self.samples().iter().map(|s| s.queue_len).max().unwrap()
+    }
+
+    pub fn peak_rss_bytes(&self) -> u64 {
+        self.samples().iter().map(|s| s.rss_bytes).max().unwrap()
+    }
+
+    /// Prints the samples, run the tests with `--nocapture` to see them.
+    pub fn report(&self) {
+        let baseline = self.baseline();
+        for sample in self.samples() {
+            println!(
+                "{:>8.1} ms: {:>7} queued, {:>+8} KiB RSS",
+                sample.at.as_secs_f64() * 1000.0,
+                sample.queue_len,
+                (sample.rss_bytes as i64 - baseline.rss_bytes as i64) / 1024
+            );
+        }
+    }
+}
+
+impl Drop for QueueMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
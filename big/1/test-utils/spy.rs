@@ -0,0 +1,73 @@
+use crate::adapter::ModuleSystem;
+use assignment_1_solution::{ModuleRef, System};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+/// Forwards every message it handles to its `Spy`, with the instant it was
+/// handled at.
+pub struct MessageSpy<M> {
+    sender: UnboundedSender<(Instant, M)>,
+}
+
+handler!([M: Send + 'static] MessageSpy<M>, M, |self, msg| {
+    /* The test may have stopped listening already */
+    let _ = self.sender.send((Instant::now(), msg));
+});
+
+/// The test side of a `MessageSpy`. Instants are measured on tokio's clock,
+/// so they are exact on a paused one.
+pub struct Spy<M> {
+    receiver: UnboundedReceiver<(Instant, M)>,
+    start: Instant,
+}
+
+impl<M: Send + 'static> Spy<M> {
+    pub async fn register(system: &mut System) -> (ModuleRef<MessageSpy<M>>, Spy<M>) {
+        let (sender, receiver) = unbounded_channel();
+        let spy_ref = system.register(MessageSpy { sender }).await;
+        (
+            spy_ref,
+            Spy {
+                receiver,
+                start: Instant::now(),
+            },
+        )
+    }
+
+    /// Waits for the next message.
+    pub async fn recv(&mut self) -> M {
+        self.receiver
+            .recv()
+            .await
+            .expect("The spy module is gone before handling a message")
+            .1
+    }
+
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Option<M> {
+        tokio::time::timeout(timeout, self.recv()).await.ok()
+    }
+
+    /// Messages handled so far and not received yet.
+    pub fn received(&mut self) -> Vec<M> {
+        self.received_at().into_iter().map(|(_, msg)| msg).collect()
+    }
+
+    /// Like `received`, with the time since the spy was registered.
+    pub fn received_at(&mut self) -> Vec<(Duration, M)> {
+        let mut messages = Vec::new();
+        while let Ok((at, msg)) = self.receiver.try_recv() {
+            messages.push((at - self.start, msg));
+        }
+        messages
+    }
+
+    pub async fn assert_silent_for(&mut self, duration: Duration) {
+        if tokio::time::timeout(duration, self.receiver.recv())
+            .await
+            .is_ok_and(|msg| msg.is_some())
+        {
+            panic!("The spy module handled a message within {:?}", duration);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use crate::adapter::ModuleSystem;
+use assignment_1_solution::{Module, ModuleRef, System};
+
+/// A new system with a clone of `module` registered. The returned module
+/// shares its state with the registered one, if it keeps the state behind
+/// an `Arc`.
+pub async fn setup_system<T: Module + Clone>(module: T) -> (System, T, ModuleRef<T>) {
+    let mut system = System::new().await;
+    let module_ref = system.register(module.clone()).await;
+
+    (system, module, module_ref)
+}
@@ -0,0 +1,28 @@
+use std::future::Future;
+use tokio::runtime::Handle;
+
+/// Asserts that no task of the current runtime is alive, other than the one
+/// of the test itself in `#[tokio::test]`, which the metrics do not count.
+/// To learn which tasks leaked, run the test with `leaks::run_leak_checked`.
+pub fn verify_workers_done() {
+    let metrics = Handle::current().metrics();
+
+    assert_eq!(metrics.num_alive_tasks(), 0);
+}
+
+/// Runs `future` in a task. A panic in it is acceptable, for example from
+/// `send()` on a system which is already shut down, and gives `None`.
+pub async fn might_panic<F>(future: F) -> Option<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let result = tokio::spawn(future).await;
+    match result {
+        Ok(output) => Some(output),
+        Err(err) => {
+            assert!(err.is_panic());
+            None
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use assignment_1_solution::System;
+use assignment_1_test_utils::spy::Spy;
+#[cfg(tokio_unstable)]
+use assignment_1_test_utils::tasks::verify_workers_done;
+use ntest::timeout;
+use std::time::Duration;
+
+// All tests here run on tokio's paused clock: time advances only when every
+// task is idle, straight to the next timer, so ticks arrive at exact instants
+// no matter how loaded the machine is. This needs the `test-util` feature of
+// tokio, see README.md.
+
+#[derive(Clone)]
+struct Tick(u8);
+
+fn received_ticks(spy: &mut Spy<Tick>) -> Vec<(u8, u64)> {
+    spy.received_at()
+        .into_iter()
+        .map(|(at, tick)| (tick.0, at.as_millis() as u64))
+        .collect()
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn ticks_arrive_at_exact_intervals() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    spy_ref
+        .request_tick(Tick(1), Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(220)).await;
+
+    assert_eq!(
+        received_ticks(&mut spy),
+        vec![(1, 50), (1, 100), (1, 150), (1, 200)]
+    );
+    system.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn stopped_timer_sends_no_more_ticks() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    let timer_handle = spy_ref
+        .request_tick(Tick(1), Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(170)).await;
+    timer_handle.stop().await;
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    assert_eq!(received_ticks(&mut spy), vec![(1, 50), (1, 100), (1, 150)]);
+    system.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn timers_of_one_module_tick_independently() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    let timer_handle_1 = spy_ref
+        .request_tick(Tick(1), Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(70)).await;
+    let timer_handle_2 = spy_ref
+        .request_tick(Tick(2), Duration::from_millis(100))
+        .await;
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    timer_handle_1.stop().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    timer_handle_2.stop().await;
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    assert_eq!(
+        received_ticks(&mut spy),
+        vec![(1, 50), (1, 100), (1, 150), (2, 170), (2, 270)]
+    );
+    system.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn stopping_one_timer_does_not_stop_others() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    let timer_handle_1 = spy_ref
+        .request_tick(Tick(1), Duration::from_millis(30))
+        .await;
+    spy_ref
+        .request_tick(Tick(2), Duration::from_millis(40))
+        .await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    timer_handle_1.stop().await;
+    tokio::time::sleep(Duration::from_millis(160)).await;
+
+    assert_eq!(
+        received_ticks(&mut spy),
+        vec![(2, 40), (2, 80), (2, 120), (2, 160)]
+    );
+    system.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn stopping_timer_twice_is_harmless() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    let timer_handle = spy_ref
+        .request_tick(Tick(1), Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    timer_handle.stop().await;
+    timer_handle.stop().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    assert_eq!(received_ticks(&mut spy), vec![(1, 50)]);
+    system.shutdown().await;
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn no_ticks_arrive_after_shutdown() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    spy_ref
+        .request_tick(Tick(1), Duration::from_millis(50))
+        .await;
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    system.shutdown().await;
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    assert_eq!(received_ticks(&mut spy), vec![(1, 50), (1, 100)]);
+}
+
+#[cfg(tokio_unstable)]
+#[tokio::test(start_paused = true, unhandled_panic = "shutdown_runtime")]
+#[timeout(1000)]
+async fn timers_stop_with_the_system() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+
+    for interval in [30, 50, 70] {
+        spy_ref
+            .request_tick(Tick(interval), Duration::from_millis(interval as u64))
+            .await;
+    }
+    tokio::time::sleep(Duration::from_millis(95)).await;
+    system.shutdown().await;
+    verify_workers_done();
+    tokio::time::sleep(Duration::from_secs(10)).await;
+
+    let mut received = received_ticks(&mut spy);
+    received.sort_by_key(|(_, at)| *at);
+    assert_eq!(
+        received,
+        vec![(30, 30), (50, 50), (30, 60), (70, 70), (30, 90)]
+    );
+}
+
+// Timers requested at 0, 100, ..., 900 ms tick every second until shutdown at
+// 3050 ms, so only the first one ticks three times.
+#[cfg(tokio_unstable)]
+#[tokio::test(start_paused = true, unhandled_panic = "shutdown_runtime")]
+#[timeout(1000)]
+async fn many_timers_take_no_real_time() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+    let real_start = std::time::Instant::now();
+
+    for _ in 0..10 {
+        spy_ref.request_tick(Tick(1), Duration::from_secs(1)).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(2050)).await;
+    system.shutdown().await;
+
+    assert_eq!(received_ticks(&mut spy).len(), 21);
+    assert!(real_start.elapsed() < Duration::from_millis(500));
+    verify_workers_done();
+}
+
+#[tokio::test(start_paused = true)]
+#[timeout(1000)]
+async fn long_intervals_take_no_real_time() {
+    let mut system = System::new().await;
+    let (spy_ref, mut spy) = Spy::register(&mut system).await;
+    let real_start = std::time::Instant::now();
+
+    spy_ref
+        .request_tick(Tick(1), Duration::from_secs(3600))
+        .await;
+    tokio::time::sleep(Duration::from_secs(24 * 3600 + 1)).await;
+
+    let ticks = received_ticks(&mut spy);
+    assert_eq!(ticks.len(), 24);
+    assert_eq!(ticks.last(), Some(&(1, 24 * 3600 * 1000)));
+    assert!(real_start.elapsed() < Duration::from_millis(500));
+    system.shutdown().await;
+}
@@ -0,0 +1,20 @@
+#!/bin/bash
+
+# Runs the interop tests on clusters mixing ranks from different solutions.
+# Usage: ./interop.sh [OTHER_ATOMIC_DISC_DRIVE_BINARY...]
+# Rank 1 runs our atomic_disc_drive, the next ranks run the given binaries
+# (e.g. a classmate's build), repeated as needed.
+
+set -e
+
+cd atomic_disc_drive
+cargo build --release
+binaries="$(pwd)/target/release/atomic_disc_drive"
+cd ../
+
+for other in "$@"; do
+    binaries="$binaries:$(realpath "$other")"
+done
+
+cd ./public-tests/
+ATOMIC_DISC_DRIVE_BINARIES="$binaries" cargo test --test interop -- --ignored
@@ -0,0 +1,266 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec, StatusCode, MAGIC_NUMBER,
+};
+use assignment_2_test_utils::client_keys::*;
+use assignment_2_test_utils::system::{
+    response_hmac_tag_is_ok, HmacSha256, RegisterResponseContent, TestProcessesConfig,
+    HMAC_TAG_SIZE,
+};
+use hmac::Mac;
+use ntest::timeout;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn wrongly_keyed_writes_get_auth_failure_signed_with_server_key() {
+    // given
+    log_init();
+    let cluster = KeyedCluster::start(3, 23310).await;
+    let config = cluster.config();
+    let server_key = cluster.client_key(1).to_vec();
+    let tenant_key = random_client_key();
+    let mut stream = config.connect(1).await;
+    /* Both tenants share one connection */
+    let commands = [
+        (write_cmd(1, 0, 1), &server_key),
+        (write_cmd(2, 0, 2), &tenant_key),
+        (write_cmd(3, 1, 3), &tenant_key),
+        (write_cmd(4, 2, 4), &server_key),
+    ];
+
+    // when
+    for (cmd, key) in &commands {
+        send_cmd_with_key(&RegisterCommand::Client(cmd.clone()), &mut stream, key).await;
+    }
+
+    // then
+    let mut statuses = Vec::new();
+    for _ in &commands {
+        let response = config.read_response(&mut stream).await.unwrap();
+        assert!(response_hmac_tag_is_ok(&response, &server_key));
+        assert!(!response_hmac_tag_is_ok(&response, &tenant_key));
+        assert!(matches!(response.content, RegisterResponseContent::Write));
+        statuses.push((
+            response.header.request_identifier,
+            response.header.status_code as u8,
+        ));
+    }
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        vec![
+            (1, StatusCode::Ok as u8),
+            (2, StatusCode::AuthFailure as u8),
+            (3, StatusCode::AuthFailure as u8),
+            (4, StatusCode::Ok as u8)
+        ]
+    );
+    /* Rejected writes have no effect */
+    for (sector_idx, value) in [(0, 1), (1, 0), (2, 4)] {
+        assert_eq!(
+            read_sector(config, &mut stream, sector_idx, &server_key).await,
+            vec![value; 4096]
+        );
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn wrongly_keyed_reads_get_auth_failure_signed_with_server_key() {
+    // given
+    log_init();
+    let cluster = KeyedCluster::start(3, 23320).await;
+    let server_key = cluster.client_key(2).to_vec();
+    let mut stream = cluster.config().connect(2).await;
+
+    // when
+    send_cmd_with_key(
+        &RegisterCommand::Client(read_cmd(77, 5)),
+        &mut stream,
+        &random_client_key(),
+    )
+    .await;
+
+    // then
+    let header = read_signed_error_response(&mut stream, &server_key).await;
+    assert_eq!(&header[0..4], MAGIC_NUMBER.as_ref());
+    assert_eq!(header[6], StatusCode::AuthFailure as u8);
+    assert_eq!(header[7], 0x41);
+    assert_eq!(u64::from_be_bytes(header[8..16].try_into().unwrap()), 77);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn data_is_readable_after_client_key_rotation() {
+    // given
+    log_init();
+    let mut cluster = KeyedCluster::start(3, 23330).await;
+    let old_key = cluster.client_key(0).to_vec();
+    let new_key = random_client_key();
+    {
+        let mut stream = cluster.config().connect(0).await;
+        for sector_idx in 0..8 {
+            write_sector(
+                cluster.config(),
+                &mut stream,
+                sector_idx,
+                sector_idx as u8 + 10,
+                &old_key,
+            )
+            .await;
+        }
+    }
+
+    // when
+    cluster.rotate_client_key(&new_key).await;
+
+    // then
+    for proc_idx in 0..3 {
+        let mut stream = cluster.config().connect(proc_idx).await;
+        for sector_idx in 0..8 {
+            assert_eq!(
+                read_sector(cluster.config(), &mut stream, sector_idx, &new_key).await,
+                vec![sector_idx as u8 + 10; 4096]
+            );
+        }
+
+        send_cmd_with_key(
+            &RegisterCommand::Client(write_cmd(100, 0, 99)),
+            &mut stream,
+            &old_key,
+        )
+        .await;
+        let response = cluster.config().read_response(&mut stream).await.unwrap();
+        assert!(matches!(
+            response.header.status_code,
+            StatusCode::AuthFailure
+        ));
+        assert!(response_hmac_tag_is_ok(&response, &new_key));
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn ranks_midway_through_key_rotation_share_registers() {
+    // given
+    log_init();
+    let mut cluster = KeyedCluster::start(3, 23340).await;
+    let old_key = cluster.client_key(0).to_vec();
+    let new_key = random_client_key();
+
+    // when
+    /* Only the first rank got the new key so far */
+    cluster.restart(0, &new_key).await;
+    let mut rotated = cluster.config().connect(0).await;
+    let mut not_rotated = cluster.config().connect(2).await;
+    write_sector(cluster.config(), &mut rotated, 3, 33, &new_key).await;
+
+    // then
+    assert_eq!(
+        read_sector(cluster.config(), &mut not_rotated, 3, &old_key).await,
+        vec![33; 4096]
+    );
+    send_cmd_with_key(
+        &RegisterCommand::Client(write_cmd(5, 3, 44)),
+        &mut rotated,
+        &old_key,
+    )
+    .await;
+    let response = cluster.config().read_response(&mut rotated).await.unwrap();
+    assert!(matches!(
+        response.header.status_code,
+        StatusCode::AuthFailure
+    ));
+    assert!(response_hmac_tag_is_ok(&response, &new_key));
+    assert_eq!(
+        read_sector(cluster.config(), &mut rotated, 3, &new_key).await,
+        vec![33; 4096]
+    );
+}
+
+/// Reads a response to a failed read, whether or not the process sends the
+/// sector content with it, and checks its HMAC tag. Returns the header.
+async fn read_signed_error_response(stream: &mut TcpStream, hmac_client_key: &[u8]) -> Vec<u8> {
+    let mut data = vec![0; 16 + HMAC_TAG_SIZE];
+    stream.read_exact(&mut data).await.unwrap();
+    if !tag_is_ok(&data, hmac_client_key) {
+        let mut content = vec![0; 4096];
+        stream.read_exact(&mut content).await.unwrap();
+        data.extend(content);
+        assert!(
+            tag_is_ok(&data, hmac_client_key),
+            "Response is not signed with the key of the process"
+        );
+    }
+    data[..16].to_vec()
+}
+
+fn tag_is_ok(data: &[u8], key: &[u8]) -> bool {
+    let boundary = data.len() - HMAC_TAG_SIZE;
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(&data[..boundary]);
+    mac.verify_slice(&data[boundary..]).is_ok()
+}
+
+async fn write_sector(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sector_idx: u64,
+    value: u8,
+    key: &[u8],
+) {
+    let cmd = write_cmd(sector_idx, sector_idx, value);
+    send_cmd_with_key(&RegisterCommand::Client(cmd), stream, key).await;
+    let response = config.read_response(stream).await.unwrap();
+    assert!(matches!(response.header.status_code, StatusCode::Ok));
+    assert!(response_hmac_tag_is_ok(&response, key));
+}
+
+async fn read_sector(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sector_idx: u64,
+    key: &[u8],
+) -> Vec<u8> {
+    let cmd = read_cmd(sector_idx + 1000, sector_idx);
+    send_cmd_with_key(&RegisterCommand::Client(cmd), stream, key).await;
+    let response = config.read_response(stream).await.unwrap();
+    assert!(matches!(response.header.status_code, StatusCode::Ok));
+    assert!(response_hmac_tag_is_ok(&response, key));
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => sector,
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+}
+
+fn write_cmd(request_identifier: u64, sector_idx: u64, value: u8) -> ClientRegisterCommand {
+    ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Write {
+            data: SectorVec(vec![value; 4096]),
+        },
+    }
+}
+
+fn read_cmd(request_identifier: u64, sector_idx: u64) -> ClientRegisterCommand {
+    ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Read,
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
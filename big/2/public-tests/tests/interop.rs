@@ -0,0 +1,56 @@
+use assignment_2_test_utils::cluster_matrix::{cluster_shapes, ClusterSource};
+use assignment_2_test_utils::external::binaries_from_env;
+use assignment_2_test_utils::scenarios;
+use ntest::timeout;
+
+/* All tests are run by interop.sh, which builds the binaries. They run the
+ * scenarios of the system tests, with rank `i + 1` of every cluster running
+ * the binary `i` modulo the number of binaries */
+
+fn mixed_binaries() -> ClusterSource {
+    ClusterSource::External(binaries_from_env())
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(120000)]
+#[ignore]
+async fn concurrent_operations_on_the_same_sector() {
+    log_init();
+    let source = mixed_binaries();
+    for shape in cluster_shapes(22810, 1) {
+        println!("{}", shape);
+        scenarios::concurrent_operations_on_the_same_sector(&source, shape).await;
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(180000)]
+#[ignore]
+async fn large_number_of_operations_execute_successfully() {
+    log_init();
+    let source = mixed_binaries();
+    for shape in cluster_shapes(22860, 1) {
+        println!("{}", shape);
+        scenarios::large_number_of_operations_execute_successfully(&source, shape).await;
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(120000)]
+#[ignore]
+async fn concurrent_writes_are_serialized() {
+    log_init();
+    let source = mixed_binaries();
+    /* The stub never answers, so the others must form a majority without it */
+    for shape in cluster_shapes(24010, 3) {
+        println!("{}", shape);
+        scenarios::concurrent_writes_are_serialized(&source, shape).await;
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
@@ -0,0 +1,172 @@
+use assignment_2_test_utils::linearizability::{CheckError, LinearizabilityChecker, INITIAL_VALUE};
+use assignment_2_test_utils::soak::{run_soak, SoakConfig};
+use ntest::timeout;
+use std::time::Duration;
+
+const SECTOR: u64 = 7;
+
+fn checker() -> LinearizabilityChecker {
+    LinearizabilityChecker::new(Duration::from_secs(60))
+}
+
+#[test]
+fn sequential_history_is_linearizable() {
+    // given
+    let mut checker = checker();
+
+    // when
+    let read = checker.invoke(0, SECTOR, None);
+    let first = checker.complete(SECTOR, read, Some(INITIAL_VALUE));
+    let write = checker.invoke(0, SECTOR, Some(1));
+    let second = checker.complete(SECTOR, write, None);
+    let read = checker.invoke(1, SECTOR, None);
+    let third = checker.complete(SECTOR, read, Some(1));
+
+    // then
+    assert!(first.is_ok() && second.is_ok() && third.is_ok());
+    assert_eq!(checker.ops_checked, 3);
+}
+
+#[test]
+fn read_concurrent_with_write_may_return_either_value() {
+    for observed in [INITIAL_VALUE, 1] {
+        // given
+        let mut checker = checker();
+        let write = checker.invoke(0, SECTOR, Some(1));
+        let read = checker.invoke(1, SECTOR, None);
+
+        // when
+        checker.complete(SECTOR, read, Some(observed)).unwrap();
+        let result = checker.complete(SECTOR, write, None);
+
+        // then
+        assert!(result.is_ok());
+    }
+}
+
+#[test]
+fn stale_read_is_a_violation() {
+    // given
+    let mut checker = checker();
+    let write = checker.invoke(0, SECTOR, Some(1));
+    checker.complete(SECTOR, write, None).unwrap();
+
+    // when
+    let read = checker.invoke(1, SECTOR, None);
+    let result = checker.complete(SECTOR, read, Some(INITIAL_VALUE));
+
+    // then
+    let Err(CheckError::NotLinearizable(violation)) = result else {
+        panic!("Expected a violation, got {:?}", result);
+    };
+    assert_eq!(violation.sector_idx, SECTOR);
+    assert_eq!(violation.possible_values, vec![1]);
+}
+
+#[test]
+fn new_old_inversion_is_a_violation() {
+    // given
+    let mut checker = checker();
+    let write = checker.invoke(0, SECTOR, Some(1));
+
+    // when
+    let first_read = checker.invoke(1, SECTOR, None);
+    checker.complete(SECTOR, first_read, Some(1)).unwrap();
+    let second_read = checker.invoke(2, SECTOR, None);
+    checker
+        .complete(SECTOR, second_read, Some(INITIAL_VALUE))
+        .unwrap();
+    let result = checker.complete(SECTOR, write, None);
+
+    // then
+    assert!(result.is_err());
+}
+
+#[test]
+fn abandoned_write_may_take_effect_later_but_only_once() {
+    // given
+    let mut checker = checker();
+    let abandoned = checker.invoke(0, SECTOR, Some(1));
+    checker.abandon(SECTOR, abandoned).unwrap();
+
+    // when
+    let read = checker.invoke(1, SECTOR, None);
+    let before_effect = checker.complete(SECTOR, read, Some(INITIAL_VALUE));
+    let read = checker.invoke(1, SECTOR, None);
+    let after_effect = checker.complete(SECTOR, read, Some(1));
+    let write = checker.invoke(2, SECTOR, Some(2));
+    checker.complete(SECTOR, write, None).unwrap();
+    let read = checker.invoke(1, SECTOR, None);
+    let second_effect = checker.complete(SECTOR, read, Some(1));
+
+    // then
+    assert!(before_effect.is_ok());
+    assert!(after_effect.is_ok());
+    assert!(second_effect.is_err());
+}
+
+#[test]
+fn sectors_are_checked_independently() {
+    // given
+    let mut checker = checker();
+    let write = checker.invoke(0, SECTOR, Some(1));
+    checker.complete(SECTOR, write, None).unwrap();
+
+    // when
+    let read = checker.invoke(1, SECTOR + 1, None);
+    let result = checker.complete(SECTOR + 1, read, Some(INITIAL_VALUE));
+
+    // then
+    assert!(result.is_ok());
+}
+
+#[test]
+fn too_many_abandoned_writes_in_a_segment_are_inconclusive() {
+    // given
+    let mut checker = checker();
+    /* Never completes, so the segment stays open */
+    checker.invoke(0, SECTOR, None);
+
+    // when
+    let results: Vec<_> = (1..=65)
+        .map(|value| {
+            let write = checker.invoke(1, SECTOR, Some(value));
+            checker.abandon(SECTOR, write)
+        })
+        .collect();
+
+    // then
+    assert!(results[..64].iter().all(Result::is_ok));
+    assert!(matches!(
+        results[64],
+        Err(CheckError::Inconclusive {
+            sector_idx: SECTOR,
+            ..
+        })
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial_test::serial]
+#[timeout(120000)]
+async fn short_soak_finds_no_violations() {
+    // given
+    let trace_dir = tempfile::tempdir().unwrap();
+    let mut config = SoakConfig::new(rand::random(), Duration::from_secs(20));
+    config.port_range_start = 22710;
+    config.fault_interval = Duration::from_secs(2);
+    config.trace_path = trace_dir.path().join("trace.txt");
+    let seed = config.seed;
+
+    // when
+    let result = run_soak(config).await;
+
+    // then
+    match result {
+        Ok(report) => {
+            assert!(report.ops_checked > 0);
+            assert!(report.faults > 0);
+        }
+        Err(failure) => panic!("Soak with seed {} failed:\n{}", seed, failure),
+    }
+}
@@ -0,0 +1,167 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec,
+};
+use assignment_2_test_utils::relay::RelayedProcesses;
+use assignment_2_test_utils::system::{RegisterResponseContent, TestProcessesConfig};
+use ntest::timeout;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn operations_complete_after_links_are_severed() {
+    // given
+    log_init();
+    let config = TestProcessesConfig::new(3, 22210);
+    let processes = RelayedProcesses::start(&config, 22220).await;
+    let mut stream = config.connect(0).await;
+    write_sectors(&config, &mut stream, 0..4, 1).await;
+    let accepted_before = processes.accepted();
+
+    // when
+    processes.sever_all();
+    write_sectors(&config, &mut stream, 0..4, 2).await;
+
+    // then
+    assert_sectors(&config, &mut stream, 0..4, 2).await;
+    assert!(processes.accepted() > accepted_before);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn pending_operations_complete_once_reconnects_are_allowed() {
+    // given
+    log_init();
+    let config = TestProcessesConfig::new(3, 22230);
+    let processes = RelayedProcesses::start(&config, 22240).await;
+    let mut stream = config.connect(0).await;
+    write_sectors(&config, &mut stream, 0..4, 1).await;
+
+    // when
+    processes.refuse_all();
+    processes.sever_all();
+    send_writes(&config, &mut stream, 0..4, 2).await;
+    let response_while_refusing = tokio::time::timeout(
+        Duration::from_millis(1000),
+        config.read_response(&mut stream),
+    )
+    .await;
+    processes.allow_all();
+
+    // then
+    assert!(
+        response_while_refusing.is_err(),
+        "Operation completed without a majority"
+    );
+    for _ in 0..4 {
+        config.read_response(&mut stream).await.unwrap();
+    }
+    assert_sectors(&config, &mut stream, 0..4, 2).await;
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(40000)]
+async fn operations_complete_when_links_break_mid_operation() {
+    // given
+    log_init();
+    let commands_total = 32;
+    let config = TestProcessesConfig::new(3, 22250);
+    let processes = Arc::new(RelayedProcesses::start(&config, 22260).await);
+    let mut stream = config.connect(1).await;
+    let churn = {
+        let processes = processes.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                processes.sever_all();
+                processes.refuse_all();
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                processes.allow_all();
+            }
+        })
+    };
+
+    // when
+    write_sectors(&config, &mut stream, 0..commands_total, 3).await;
+    churn.abort();
+    processes.allow_all();
+
+    // then
+    assert_sectors(&config, &mut stream, 0..commands_total, 3).await;
+}
+
+async fn send_writes(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sectors: std::ops::Range<u64>,
+    value: u8,
+) {
+    for sector_idx in sectors {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: sector_idx,
+                        sector_idx,
+                    },
+                    content: ClientRegisterCommandContent::Write {
+                        data: SectorVec(vec![value; 4096]),
+                    },
+                }),
+                stream,
+            )
+            .await;
+    }
+}
+
+async fn write_sectors(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sectors: std::ops::Range<u64>,
+    value: u8,
+) {
+    send_writes(config, stream, sectors.clone(), value).await;
+    for _ in sectors {
+        config.read_response(stream).await.unwrap();
+    }
+}
+
+async fn assert_sectors(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sectors: std::ops::Range<u64>,
+    value: u8,
+) {
+    for sector_idx in sectors.clone() {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: sector_idx,
+                        sector_idx,
+                    },
+                    content: ClientRegisterCommandContent::Read,
+                }),
+                stream,
+            )
+            .await;
+    }
+    for _ in sectors {
+        let response = config.read_response(stream).await.unwrap();
+        match response.content {
+            RegisterResponseContent::Read(SectorVec(sector)) => {
+                assert_eq!(sector, vec![value; 4096])
+            }
+            RegisterResponseContent::Write => panic!("Expected read response"),
+        }
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
@@ -1,18 +1,17 @@
 use assignment_2_solution::{
-    deserialize_register_command, run_register_process, serialize_register_command,
-    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, Configuration,
-    PublicConfiguration, RegisterCommand, SectorVec, SystemRegisterCommandContent, MAGIC_NUMBER,
-};
-use assignment_2_test_utils::system::{
-    HmacSha256, RegisterResponseContent, TestProcessesConfig, HMAC_TAG_SIZE,
+    run_register_process, serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, Configuration, PublicConfiguration, RegisterCommand, SectorVec,
+    MAGIC_NUMBER,
 };
+use assignment_2_test_utils::cluster_matrix::{cluster_shapes, ClusterSource};
+use assignment_2_test_utils::scenarios;
+use assignment_2_test_utils::system::{HmacSha256, HMAC_TAG_SIZE};
 use hmac::Mac;
 use ntest::timeout;
-use std::collections::HashMap;
 use tempfile::tempdir;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
+    net::TcpStream,
     time::Duration,
 };
 
@@ -138,111 +137,12 @@ async fn bad_sector_number() {
 #[serial_test::serial]
 #[timeout(30000)]
 async fn concurrent_writes_are_serialized() {
-    // given
     log_init();
-    let port_range_start = 21518;
-    let n_clients = 16;
-    /* Spawn two and add our stub to system */
-    let config = TestProcessesConfig::new(3, port_range_start);
-    tokio::spawn(run_register_process(config.config(0)));
-    tokio::spawn(run_register_process(config.config(1)));
-    let listener = TcpListener::bind(config.tcp_locations[2].clone())
-        .await
-        .unwrap();
-    tokio::time::sleep(Duration::from_millis(300)).await;
-
-    let mut streams = Vec::new();
-    for _ in 0..n_clients {
-        streams.push(config.connect(0).await);
-    }
-    // when
-    for (i, stream) in streams.iter_mut().enumerate() {
-        config
-            .send_cmd(
-                &RegisterCommand::Client(ClientRegisterCommand {
-                    header: ClientCommandHeader {
-                        request_identifier: i.try_into().unwrap(),
-                        sector_idx: 0,
-                    },
-                    content: ClientRegisterCommandContent::Write {
-                        data: SectorVec(vec![if i % 2 == 0 { 1 } else { 254 }; 4096]),
-                    },
-                }),
-                stream,
-            )
-            .await;
-    }
-
-    let mut receivers = Vec::new();
-    for _ in 0..2 {
-        let (receiver, _addr) = listener.accept().await.unwrap();
-        receivers.push(receiver);
-    }
-
-    for stream in &mut streams {
-        config.read_response(stream).await.unwrap();
-    }
-
-    config
-        .send_cmd(
-            &RegisterCommand::Client(ClientRegisterCommand {
-                header: ClientCommandHeader {
-                    request_identifier: n_clients,
-                    sector_idx: 0,
-                },
-                content: ClientRegisterCommandContent::Read,
-            }),
-            &mut streams[0],
-        )
-        .await;
-    let response = config.read_response(&mut streams[0]).await.unwrap();
-
-    match response.content {
-        RegisterResponseContent::Read(SectorVec(sector)) => {
-            assert!(sector == vec![1; 4096] || sector == vec![254; 4096]);
-        }
-        RegisterResponseContent::Write => panic!("Expected read response"),
-    }
-
-    // then
-    let mut receiving_set = tokio::task::JoinSet::new();
-    for _ in 0..2 {
-        let mut receiver = receivers.pop().unwrap();
-        let hmac_system_key: [u8; 64] = config.hmac_system_key.clone().try_into().unwrap();
-        let hmac_client_key: [u8; 32] = config.hmac_client_key.clone().try_into().unwrap();
-
-        receiving_set.spawn(async move {
-            let mut data_written: HashMap<u64, SectorVec> = HashMap::new();
-            loop {
-                let (message, _) =
-                    deserialize_register_command(&mut receiver, &hmac_system_key, &hmac_client_key)
-                        .await
-                        .unwrap();
-                let RegisterCommand::System(cmd) = message else {
-                    continue;
-                };
-                let SystemRegisterCommandContent::WriteProc {
-                    timestamp,
-                    write_rank: _,
-                    data_to_write,
-                } = cmd.content
-                else {
-                    continue;
-                };
-
-                if let Some(val) = data_written.get(&timestamp) {
-                    assert_eq!(val.0, data_to_write.0);
-                }
-
-                data_written.insert(timestamp, data_to_write);
-                if timestamp >= n_clients {
-                    break;
-                }
-            }
-        });
+    /* The stub never answers, so the others must form a majority without it */
+    for shape in cluster_shapes(21518, 3) {
+        println!("{}", shape);
+        scenarios::concurrent_writes_are_serialized(&ClusterSource::Linked, shape).await;
     }
-    // one of these tasks will get stuck in deserialize_register_command, it's ok
-    receiving_set.join_next().await;
 }
 
 fn log_init() {
@@ -0,0 +1,119 @@
+use assignment_2_test_utils::abusive_client::*;
+use assignment_2_test_utils::system::{PanicWatch, TestProcessesConfig};
+use ntest::timeout;
+use std::time::Duration;
+
+/* Upper bound for a write followed by a read of a well-behaved client */
+const LATENCY_BOUND: Duration = Duration::from_millis(2000);
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn half_sent_frames_do_not_block_other_clients() {
+    // given
+    log_init();
+    let panics = PanicWatch::start();
+    let config = TestProcessesConfig::new(3, 22310);
+    config.start().await;
+    let mut hanging = Vec::new();
+
+    // when
+    for proc_idx in 0..3 {
+        for _ in 0..8 {
+            hanging.push(send_half_frame(&config, proc_idx).await);
+        }
+    }
+
+    // then
+    assert_well_behaved_client_is_served(&config).await;
+    panics.assert_no_panics();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn clients_disconnecting_mid_operation_do_not_break_processes() {
+    // given
+    log_init();
+    let panics = PanicWatch::start();
+    let config = TestProcessesConfig::new(3, 22320);
+    config.start().await;
+
+    // when
+    for request_identifier in 0..48 {
+        send_and_disconnect(
+            &config,
+            (request_identifier % 3) as usize,
+            &write_cmd(request_identifier, request_identifier % 4, 21),
+        )
+        .await;
+    }
+    /* Responses to the disconnected clients are sent in the meantime */
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // then
+    assert_well_behaved_client_is_served(&config).await;
+    panics.assert_no_panics();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn slow_reader_does_not_block_other_clients() {
+    // given
+    log_init();
+    let panics = PanicWatch::start();
+    let config = TestProcessesConfig::new(3, 22330);
+    config.start().await;
+
+    // when
+    /* 1024 read responses are over 4 MiB, more than socket buffers can take */
+    let _slow_reader = SlowReader::start(&config, 0, 0..8, 1024, Duration::from_secs(1)).await;
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+
+    // then
+    assert_well_behaved_client_is_served(&config).await;
+    panics.assert_no_panics();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(60000)]
+async fn thousands_of_idle_connections_do_not_block_other_clients() {
+    // given
+    log_init();
+    raise_open_files_limit();
+    let panics = PanicWatch::start();
+    let config = TestProcessesConfig::new(3, 22340);
+    config.start().await;
+    let mut idle = Vec::new();
+
+    // when
+    for proc_idx in 0..3 {
+        idle.extend(open_idle_connections(&config, proc_idx, 1000).await);
+    }
+
+    // then
+    assert_well_behaved_client_is_served(&config).await;
+    panics.assert_no_panics();
+}
+
+async fn assert_well_behaved_client_is_served(config: &TestProcessesConfig) {
+    for proc_idx in 0..3 {
+        let mut stream = config.connect(proc_idx).await;
+        for sector_idx in 0..4 {
+            let latency =
+                timed_write_read(config, &mut stream, sector_idx, proc_idx as u8 + 1).await;
+            assert!(
+                latency < LATENCY_BOUND,
+                "Well-behaved client waited {:?} on process {}",
+                latency,
+                proc_idx
+            );
+        }
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
@@ -0,0 +1,244 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec,
+};
+use assignment_2_test_utils::external::{read_config_file, write_config_file};
+use assignment_2_test_utils::nbd::*;
+use assignment_2_test_utils::system::{RegisterResponseContent, TestProcessesConfig};
+use ntest::timeout;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+const N_SECTORS: u64 = 1024;
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn client_negotiates_export() {
+    // given
+    log_init();
+    let (_config, addr) = start_nbd_server(23110).await;
+
+    // when
+    let exports = NbdClient::list_exports(addr).await.unwrap();
+    let client = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    let default_export = NbdClient::connect(addr, "").await.unwrap();
+    let old_style = NbdClient::connect_with_export_name(addr, DEFAULT_EXPORT_NAME)
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(exports, vec![DEFAULT_EXPORT_NAME.to_string()]);
+    for client in [&client, &default_export, &old_style] {
+        assert_eq!(client.size(), N_SECTORS * SECTOR_SIZE);
+        assert_ne!(
+            client.transmission_flags() & TRANSMISSION_FLAG_SEND_FLUSH,
+            0
+        );
+    }
+    assert!(NbdClient::connect(addr, "no_such_export").await.is_err());
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn writes_through_nbd_land_in_register_sectors() {
+    // given
+    log_init();
+    let (config, addr) = start_nbd_server(23120).await;
+    let mut client = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    let data: Vec<u8> = (0..3 * SECTOR_SIZE).map(|idx| (idx / 7) as u8).collect();
+
+    // when
+    client.write(5 * SECTOR_SIZE, &data).await.unwrap();
+    client.flush().await.unwrap();
+
+    // then
+    assert_eq!(
+        client
+            .read(5 * SECTOR_SIZE, data.len() as u32)
+            .await
+            .unwrap(),
+        data
+    );
+    for (idx, sector_idx) in (5..8).enumerate() {
+        assert_eq!(
+            read_sector(&config, sector_idx).await,
+            data[idx * SECTOR_SIZE as usize..(idx + 1) * SECTOR_SIZE as usize]
+        );
+    }
+    client.disconnect().await.unwrap();
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn unaligned_writes_preserve_surrounding_bytes() {
+    // given
+    log_init();
+    let (_config, addr) = start_nbd_server(23130).await;
+    let mut client = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    client.write(0, &[1; 3 * 4096]).await.unwrap();
+
+    // when
+    /* Spans the boundary of sectors 0 and 1 */
+    client.write(4000, &[2; 200]).await.unwrap();
+    /* Within sector 2 */
+    client.write(8192 + 512, &[3; 512]).await.unwrap();
+
+    // then
+    let mut expected = vec![1; 3 * 4096];
+    expected[4000..4200].fill(2);
+    expected[8192 + 512..8192 + 1024].fill(3);
+    assert_eq!(client.read(0, 3 * 4096).await.unwrap(), expected);
+    assert_eq!(client.read(3990, 20).await.unwrap(), expected[3990..4010]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn concurrent_sub_sector_writes_of_two_clients_both_land() {
+    // given
+    log_init();
+    let (_config, addr) = start_nbd_server(23170).await;
+    /* The server hands out processes in turn, so they use different ones */
+    let mut first = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    let mut second = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+
+    for round in 0..50_u8 {
+        // when
+        /* Halves of the same sector, each written with a read-modify-write */
+        let (first_half, second_half) = ([round; 2048], [round + 100; 2048]);
+        let (first_write, second_write) = tokio::join!(
+            first.write(3 * 4096, &first_half),
+            second.write(3 * 4096 + 2048, &second_half),
+        );
+        first_write.unwrap();
+        second_write.unwrap();
+
+        // then
+        let mut expected = vec![round; 4096];
+        expected[2048..].fill(round + 100);
+        assert_eq!(
+            first.read(3 * 4096, 4096).await.unwrap(),
+            expected,
+            "A write of round {} was lost",
+            round
+        );
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn requests_beyond_end_of_export_fail() {
+    // given
+    log_init();
+    let (_config, addr) = start_nbd_server(23140).await;
+    let mut client = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    let size = client.size();
+
+    // when
+    let read = client.read(size - 4096, 8192).await;
+    let write = client.write(size, &[1; 4096]).await;
+
+    // then
+    assert_eq!(read.unwrap_err().raw_os_error(), Some(NBD_EINVAL as i32));
+    assert_eq!(write.unwrap_err().raw_os_error(), Some(NBD_ENOSPC as i32));
+    /* The connection is still usable */
+    client.write(size - 4096, &[9; 4096]).await.unwrap();
+    assert_eq!(client.read(size - 4096, 4096).await.unwrap(), vec![9; 4096]);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn nbd_servers_of_different_ranks_share_the_drive() {
+    // given
+    log_init();
+    let config = Arc::new(TestProcessesConfig::new(3, 23150).with_n_sectors(N_SECTORS));
+    config.start().await;
+    let first = spawn_nbd_server(config.clone(), Some(0)).await;
+    let second = spawn_nbd_server(config.clone(), Some(2)).await;
+    let mut first = NbdClient::connect(first, DEFAULT_EXPORT_NAME)
+        .await
+        .unwrap();
+    let mut second = NbdClient::connect(second, DEFAULT_EXPORT_NAME)
+        .await
+        .unwrap();
+
+    // when
+    first.write(12 * 4096, &[42; 2 * 4096]).await.unwrap();
+
+    // then
+    assert_eq!(
+        second.read(12 * 4096, 2 * 4096).await.unwrap(),
+        vec![42; 2 * 4096]
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn nbd_server_uses_cluster_of_config_file() {
+    // given
+    log_init();
+    let mut config = TestProcessesConfig::new(3, 23160).with_n_sectors(N_SECTORS);
+    /* Keys of atomic_disc_drive config files are text */
+    config.hmac_system_key = vec![b'a'; 64];
+    config.hmac_client_key = vec![b'b'; 32];
+    config.start().await;
+    let config_dir = tempfile::tempdir().unwrap();
+    let path = config_dir.path().join("config");
+    write_config_file(&config, &path).unwrap();
+
+    // when
+    let read_config = read_config_file(&path).unwrap();
+    let addr = spawn_nbd_server(Arc::new(read_config), None).await;
+    let mut client = NbdClient::connect(addr, DEFAULT_EXPORT_NAME).await.unwrap();
+    client.write(4096, &[7; 4096]).await.unwrap();
+
+    // then
+    assert_eq!(client.size(), N_SECTORS * SECTOR_SIZE);
+    assert_eq!(read_sector(&config, 1).await, vec![7; 4096]);
+}
+
+async fn start_nbd_server(port_range_start: u16) -> (Arc<TestProcessesConfig>, SocketAddr) {
+    let config = Arc::new(TestProcessesConfig::new(3, port_range_start).with_n_sectors(N_SECTORS));
+    config.start().await;
+    let addr = spawn_nbd_server(config.clone(), None).await;
+    (config, addr)
+}
+
+async fn spawn_nbd_server(config: Arc<TestProcessesConfig>, rank: Option<usize>) -> SocketAddr {
+    let server = NbdServer::bind("127.0.0.1:0", config, DEFAULT_EXPORT_NAME, rank)
+        .await
+        .unwrap();
+    let addr = server.local_addr();
+    tokio::spawn(server.run());
+    addr
+}
+
+async fn read_sector(config: &TestProcessesConfig, sector_idx: u64) -> Vec<u8> {
+    let mut stream = config.connect(1).await;
+    let cmd = ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier: sector_idx,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Read,
+    };
+    config
+        .send_cmd(&RegisterCommand::Client(cmd.clone()), &mut stream)
+        .await;
+    let response = config.read_response(&mut stream).await.unwrap();
+    config.assert_response_header(&response, &cmd);
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => sector,
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
@@ -0,0 +1,255 @@
+use assignment_2_solution::{
+    serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec, StatusCode,
+};
+use assignment_2_test_utils::system::{
+    response_hmac_tag_is_ok, RegisterResponse, RegisterResponseContent, TestProcessesConfig,
+};
+use ntest::timeout;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::Duration;
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(60000)]
+async fn pipelined_responses_echo_every_identifier_exactly_once() {
+    // given
+    log_init();
+    let config = Arc::new(TestProcessesConfig::new(3, 23210));
+    config.start().await;
+    let commands = random_commands(&random_identifiers(500), 0..64);
+
+    // when
+    let stream = config.connect(1).await;
+    let responses = pipeline(config.clone(), stream, commands.clone()).await;
+
+    // then
+    assert_responses_match(&commands, &responses);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(60000)]
+async fn pipelined_responses_stay_on_their_connection() {
+    // given
+    log_init();
+    let config = Arc::new(TestProcessesConfig::new(3, 23220));
+    config.start().await;
+    /* Every connection uses the same identifiers, but its own sectors */
+    let identifiers = random_identifiers(100);
+    let commands: Vec<_> = (0..4)
+        .map(|conn_idx| random_commands(&identifiers, conn_idx * 16..(conn_idx + 1) * 16))
+        .collect();
+
+    // when
+    let mut pipelines = Vec::new();
+    for (conn_idx, commands) in commands.iter().enumerate() {
+        let stream = config.connect(conn_idx % 3).await;
+        pipelines.push(tokio::spawn(pipeline(
+            config.clone(),
+            stream,
+            commands.clone(),
+        )));
+    }
+
+    // then
+    for (commands, pipeline) in commands.iter().zip(pipelines) {
+        assert_responses_match(commands, &pipeline.await.unwrap());
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(20000)]
+async fn extreme_request_identifiers_are_echoed() {
+    // given
+    log_init();
+    let config = Arc::new(TestProcessesConfig::new(3, 23230));
+    config.start().await;
+    let identifiers = [
+        0,
+        1,
+        u32::MAX as u64,
+        u32::MAX as u64 + 1,
+        1 << 63,
+        u64::MAX - 1,
+        u64::MAX,
+        0x0102_0304_0506_0708,
+    ];
+    let commands = random_commands(&identifiers, 0..4);
+
+    // when
+    let stream = config.connect(0).await;
+    let responses = pipeline(config.clone(), stream, commands.clone()).await;
+
+    // then
+    assert_responses_match(&commands, &responses);
+}
+
+/// Distinct identifiers in random order, far apart so that an off-by-one or
+/// a truncated identifier does not match another command.
+fn random_identifiers(count: usize) -> Vec<u64> {
+    let mut identifiers = HashSet::new();
+    while identifiers.len() < count {
+        identifiers.insert(rand::thread_rng().gen::<u64>() & !0xff);
+    }
+    let mut identifiers: Vec<u64> = identifiers.into_iter().collect();
+    identifiers.shuffle(&mut rand::thread_rng());
+    identifiers
+}
+
+/// A random mix of reads and writes, the data written is derived from the
+/// identifier.
+fn random_commands(
+    identifiers: &[u64],
+    sectors: std::ops::Range<u64>,
+) -> Vec<ClientRegisterCommand> {
+    identifiers
+        .iter()
+        .map(|&request_identifier| ClientRegisterCommand {
+            header: ClientCommandHeader {
+                request_identifier,
+                sector_idx: rand::thread_rng().gen_range(sectors.clone()),
+            },
+            content: if rand::thread_rng().gen_bool(0.5) {
+                ClientRegisterCommandContent::Read
+            } else {
+                ClientRegisterCommandContent::Write {
+                    data: SectorVec(vec![value_of(request_identifier); 4096]),
+                }
+            },
+        })
+        .collect()
+}
+
+fn value_of(request_identifier: u64) -> u8 {
+    (request_identifier
+        .to_be_bytes()
+        .iter()
+        .map(|&b| b as u64)
+        .sum::<u64>()
+        % 255) as u8
+        + 1
+}
+
+/// Sends all commands without waiting for responses and collects one
+/// response per command, failing on duplicates and unknown identifiers.
+/// Afterwards, no further response may arrive.
+async fn pipeline(
+    config: Arc<TestProcessesConfig>,
+    stream: TcpStream,
+    commands: Vec<ClientRegisterCommand>,
+) -> HashMap<u64, RegisterResponse> {
+    let (mut read_half, mut write_half) = stream.into_split();
+    let sent: HashSet<u64> = commands
+        .iter()
+        .map(|cmd| cmd.header.request_identifier)
+        .collect();
+    let commands_count = commands.len();
+    {
+        let config = config.clone();
+        /* Commands are written by another task, so that the process never
+         * blocks on writing responses while we block on writing commands */
+        tokio::spawn(async move {
+            for cmd in commands {
+                let mut data = Vec::new();
+                serialize_register_command(
+                    &RegisterCommand::Client(cmd),
+                    &mut data,
+                    &config.hmac_client_key,
+                )
+                .await
+                .unwrap();
+                write_half.write_all(&data).await.unwrap();
+            }
+            /* Keep the connection open until all responses are read */
+            std::future::pending::<()>().await;
+        });
+    }
+
+    let mut responses = HashMap::new();
+    while responses.len() < commands_count {
+        let response = config.read_response(&mut read_half).await.unwrap();
+        let request_identifier = response.header.request_identifier;
+        assert!(
+            sent.contains(&request_identifier),
+            "Response to unknown request {:#x}",
+            request_identifier
+        );
+        assert!(
+            response_hmac_tag_is_ok(&response, &config.hmac_client_key),
+            "Invalid HMAC tag of response to {:#x}",
+            request_identifier
+        );
+        assert!(
+            responses.insert(request_identifier, response).is_none(),
+            "Duplicate response to {:#x}",
+            request_identifier
+        );
+    }
+
+    let extra = tokio::time::timeout(
+        Duration::from_millis(500),
+        config.read_response(&mut read_half),
+    )
+    .await;
+    assert!(extra.is_err(), "Response after all requests were answered");
+    responses
+}
+
+fn assert_responses_match(
+    commands: &[ClientRegisterCommand],
+    responses: &HashMap<u64, RegisterResponse>,
+) {
+    assert_eq!(responses.len(), commands.len());
+    let mut written: HashMap<u64, HashSet<u8>> = HashMap::new();
+    for cmd in commands {
+        if let ClientRegisterCommandContent::Write { data } = &cmd.content {
+            written
+                .entry(cmd.header.sector_idx)
+                .or_default()
+                .insert(data.0[0]);
+        }
+    }
+
+    for cmd in commands {
+        let response = &responses[&cmd.header.request_identifier];
+        assert!(matches!(response.header.status_code, StatusCode::Ok));
+        match (&cmd.content, &response.content) {
+            (
+                ClientRegisterCommandContent::Read,
+                RegisterResponseContent::Read(SectorVec(data)),
+            ) => {
+                /* Reads race with the writes of the same pipeline */
+                let allowed = written.get(&cmd.header.sector_idx);
+                assert!(
+                    data.iter().all(|&b| b == data[0])
+                        && (data[0] == 0
+                            || allowed.is_some_and(|values| values.contains(&data[0]))),
+                    "Read of sector {} returned data no one wrote",
+                    cmd.header.sector_idx
+                );
+            }
+            (ClientRegisterCommandContent::Write { .. }, RegisterResponseContent::Write) => {}
+            (ClientRegisterCommandContent::Read, RegisterResponseContent::Write) => panic!(
+                "Read {:#x} got a write response (0x42)",
+                cmd.header.request_identifier
+            ),
+            (ClientRegisterCommandContent::Write { .. }, RegisterResponseContent::Read(_)) => {
+                panic!(
+                    "Write {:#x} got a read response (0x41)",
+                    cmd.header.request_identifier
+                )
+            }
+        }
+    }
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
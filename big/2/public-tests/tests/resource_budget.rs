@@ -0,0 +1,59 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec, StatusCode,
+};
+use assignment_2_test_utils::resources::{ResourceBudget, ResourceMonitor};
+use assignment_2_test_utils::system::TestProcessesConfig;
+use ntest::timeout;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+const N_SECTORS: u64 = 4_000_000;
+const DISTINCT_SECTORS: u64 = 20_000;
+const CLIENTS: u64 = 16;
+
+#[tokio::test(flavor = "multi_thread")]
+#[serial_test::serial]
+#[timeout(300000)]
+async fn many_touched_sectors_do_not_exhaust_memory_or_descriptors() {
+    // given
+    let budget = ResourceBudget::default();
+    let monitor = ResourceMonitor::start(Duration::from_millis(200));
+    let config = Arc::new(TestProcessesConfig::new(1, 22610).with_n_sectors(N_SECTORS));
+    config.start().await;
+
+    // when
+    let clients: Vec<_> = (0..CLIENTS)
+        .map(|client_idx| {
+            let config = config.clone();
+            tokio::spawn(async move {
+                let mut stream = config.connect(0).await;
+                /* Sectors are spread over the drive, no two clients share one */
+                for i in (client_idx..DISTINCT_SECTORS).step_by(CLIENTS as usize) {
+                    let cmd = RegisterCommand::Client(ClientRegisterCommand {
+                        header: ClientCommandHeader {
+                            request_identifier: i,
+                            sector_idx: i * (N_SECTORS / DISTINCT_SECTORS),
+                        },
+                        content: ClientRegisterCommandContent::Write {
+                            data: SectorVec(vec![i as u8; 4096]),
+                        },
+                    });
+                    config.send_cmd(&cmd, &mut stream).await;
+                    let response = config.read_response(&mut stream).await.unwrap();
+                    assert!(matches!(response.header.status_code, StatusCode::Ok));
+                }
+            })
+        })
+        .collect();
+    for client in clients {
+        client.await.unwrap();
+    }
+
+    // then
+    monitor
+        .assert_settles(Duration::from_secs(3), &budget)
+        .await;
+    monitor.report();
+    monitor.assert_within(&budget);
+}
@@ -0,0 +1,122 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec,
+};
+use assignment_2_test_utils::lossy_peer::LossyPeer;
+use assignment_2_test_utils::system::{RegisterResponseContent, TestProcessesConfig};
+use ntest::timeout;
+use std::time::Duration;
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn operations_complete_when_first_copies_are_lost() {
+    // given
+    log_init();
+    let port_range_start = 22110;
+    let config = TestProcessesConfig::new(3, port_range_start);
+    /* Only rank 1 is real, so every majority needs an answer from a stub */
+    let peers = [
+        LossyPeer::start(&config, 1, 2).await,
+        LossyPeer::start(&config, 2, 2).await,
+    ];
+    config.start_some([0]).await;
+    let mut stream = config.connect(0).await;
+    let write_cmd = ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier: 1,
+            sector_idx: 5,
+        },
+        content: ClientRegisterCommandContent::Write {
+            data: SectorVec(vec![7; 4096]),
+        },
+    };
+    let read_cmd = ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier: 2,
+            sector_idx: 5,
+        },
+        content: ClientRegisterCommandContent::Read,
+    };
+
+    // when
+    config
+        .send_cmd(&RegisterCommand::Client(write_cmd.clone()), &mut stream)
+        .await;
+    let write_response = config.read_response(&mut stream).await.unwrap();
+    config
+        .send_cmd(&RegisterCommand::Client(read_cmd.clone()), &mut stream)
+        .await;
+    let read_response = config.read_response(&mut stream).await.unwrap();
+
+    // then
+    config.assert_response_header(&write_response, &write_cmd);
+    config.assert_response_header(&read_response, &read_cmd);
+    match read_response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => assert_eq!(sector, vec![7; 4096]),
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+    assert!(peers.iter().map(LossyPeer::discarded_total).sum::<usize>() > 0);
+    assert!(peers.iter().map(LossyPeer::answered_total).sum::<usize>() > 0);
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(40000)]
+async fn concurrent_operations_complete_with_lossy_peers() {
+    // given
+    log_init();
+    let port_range_start = 22120;
+    let sectors_count = 8;
+    let config = TestProcessesConfig::new(3, port_range_start);
+    let peers = [
+        LossyPeer::start(&config, 1, 1).await,
+        LossyPeer::start(&config, 2, 1).await,
+    ];
+    config.start_some([0]).await;
+    let mut stream = config.connect(0).await;
+
+    // when
+    for sector_idx in 0..sectors_count {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: sector_idx,
+                        sector_idx,
+                    },
+                    content: ClientRegisterCommandContent::Write {
+                        data: SectorVec(vec![sector_idx as u8; 4096]),
+                    },
+                }),
+                &mut stream,
+            )
+            .await;
+    }
+    let mut completed = Vec::new();
+    for _ in 0..sectors_count {
+        let response = config.read_response(&mut stream).await.unwrap();
+        completed.push(response.header.request_identifier);
+    }
+
+    // then
+    completed.sort();
+    assert_eq!(completed, (0..sectors_count).collect::<Vec<_>>());
+
+    let delays: Vec<Duration> = peers
+        .iter()
+        .flat_map(LossyPeer::retransmission_delays)
+        .collect();
+    assert!(!delays.is_empty());
+    println!(
+        "Retransmission took from {:?} to {:?} (mean {:?}) over {} messages",
+        delays.iter().min().unwrap(),
+        delays.iter().max().unwrap(),
+        delays.iter().sum::<Duration>() / delays.len() as u32,
+        delays.len()
+    );
+}
+
+fn log_init() {
+    let _ = env_logger::builder().is_test(true).try_init();
+}
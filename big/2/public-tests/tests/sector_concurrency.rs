@@ -0,0 +1,79 @@
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec,
+};
+use assignment_2_test_utils::lossy_peer::LossyPeer;
+use assignment_2_test_utils::system::TestProcessesConfig;
+use ntest::timeout;
+use std::time::{Duration, Instant};
+
+const STUCK_SECTOR_DELAY: Duration = Duration::from_millis(3000);
+/* Operations on other sectors must finish well before the stuck one */
+const OTHER_SECTORS_BOUND: Duration = Duration::from_millis(1000);
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(15000)]
+async fn process_serves_other_sectors_while_one_is_stuck() {
+    // given
+    let other_sectors: Vec<u64> = (1..=8).collect();
+    let config = TestProcessesConfig::new(3, 22410);
+    /* Only rank 1 is real, and both stubs hold answers about sector 0 */
+    let peers = [
+        LossyPeer::start(&config, 1, 0).await,
+        LossyPeer::start(&config, 2, 0).await,
+    ];
+    for peer in &peers {
+        peer.delay_sector(0, STUCK_SECTOR_DELAY);
+    }
+    config.start_some([0]).await;
+    let mut stuck_stream = config.connect(0).await;
+    let mut stream = config.connect(0).await;
+
+    // when
+    let start = Instant::now();
+    config
+        .send_cmd(&RegisterCommand::Client(write_cmd(0, 0)), &mut stuck_stream)
+        .await;
+    for &sector_idx in &other_sectors {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(write_cmd(sector_idx, sector_idx)),
+                &mut stream,
+            )
+            .await;
+    }
+    let mut latencies = vec![Duration::ZERO; other_sectors.len()];
+    for _ in &other_sectors {
+        let response = config.read_response(&mut stream).await.unwrap();
+        latencies[response.header.request_identifier as usize - 1] = start.elapsed();
+    }
+    config.read_response(&mut stuck_stream).await.unwrap();
+    let stuck_latency = start.elapsed();
+
+    // then
+    report(&other_sectors, &latencies, stuck_latency);
+    assert!(latencies
+        .iter()
+        .all(|latency| *latency < OTHER_SECTORS_BOUND));
+    assert!(stuck_latency >= STUCK_SECTOR_DELAY);
+}
+
+fn write_cmd(request_identifier: u64, sector_idx: u64) -> ClientRegisterCommand {
+    ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Write {
+            data: SectorVec(vec![sector_idx as u8; 4096]),
+        },
+    }
+}
+
+fn report(other_sectors: &[u64], latencies: &[Duration], stuck_latency: Duration) {
+    println!("stuck sector 0 completed after {:?}", stuck_latency);
+    for (sector_idx, latency) in other_sectors.iter().zip(latencies) {
+        println!("sector {} completed after {:?}", sector_idx, latency);
+    }
+}
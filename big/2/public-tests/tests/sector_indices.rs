@@ -0,0 +1,324 @@
+use assignment_2_solution::{
+    build_sectors_manager, deserialize_register_command, serialize_register_command,
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec, StatusCode, SystemCommandHeader, SystemRegisterCommand,
+    SystemRegisterCommandContent, MAGIC_NUMBER,
+};
+use assignment_2_test_utils::sector_indices::*;
+use assignment_2_test_utils::system::{RegisterResponseContent, TestProcessesConfig};
+use ntest::timeout;
+use std::convert::TryInto;
+use tempfile::tempdir;
+use tokio::io::AsyncReadExt;
+use uuid::Uuid;
+
+/* Same as in simple_config */
+const LARGE_N_SECTORS: u64 = 1_000_000;
+
+#[tokio::test]
+#[timeout(5000)]
+async fn sectors_manager_stores_extreme_indices() {
+    // given
+    let root_drive_dir = tempdir().unwrap();
+    let sectors_manager = build_sectors_manager(root_drive_dir.path().to_path_buf()).await;
+    let indices = extreme_sector_indices();
+
+    // when
+    for (i, sector_idx) in indices.iter().enumerate() {
+        sectors_manager
+            .write(
+                *sector_idx,
+                &(SectorVec(vec![i as u8 + 1; 4096]), i as u64 + 1, 1),
+            )
+            .await;
+    }
+
+    // then
+    for (i, sector_idx) in indices.iter().enumerate() {
+        assert_eq!(
+            sectors_manager.read_data(*sector_idx).await.0,
+            vec![i as u8 + 1; 4096],
+            "Sector {} holds wrong data",
+            sector_idx
+        );
+        assert_eq!(
+            sectors_manager.read_metadata(*sector_idx).await,
+            (i as u64 + 1, 1)
+        );
+    }
+}
+
+#[tokio::test]
+#[timeout(5000)]
+async fn unwritten_sectors_next_to_written_ones_are_zeroed() {
+    // given
+    let root_drive_dir = tempdir().unwrap();
+    let sectors_manager = build_sectors_manager(root_drive_dir.path().to_path_buf()).await;
+    let written = [u64::MAX, 1 << 63, u64::from(u32::MAX)];
+
+    // when
+    for sector_idx in written {
+        sectors_manager
+            .write(sector_idx, &(SectorVec(vec![0xff; 4096]), 3, 2))
+            .await;
+    }
+
+    // then
+    let neighbours = [
+        u64::MAX - 1,
+        (1 << 63) - 1,
+        (1 << 63) + 1,
+        u64::from(u32::MAX) - 1,
+        u64::from(u32::MAX) + 1,
+        0,
+    ];
+    for sector_idx in neighbours {
+        assert_eq!(
+            sectors_manager.read_data(sector_idx).await.0,
+            vec![0; 4096],
+            "Unwritten sector {} is not zeroed",
+            sector_idx
+        );
+        assert_eq!(sectors_manager.read_metadata(sector_idx).await, (0, 0));
+    }
+}
+
+#[tokio::test]
+#[timeout(2000)]
+async fn extreme_indices_survive_serialization() {
+    for sector_idx in extreme_sector_indices()
+        .into_iter()
+        .chain(invalid_sector_indices(LARGE_N_SECTORS))
+    {
+        // given
+        let client_cmd = RegisterCommand::Client(ClientRegisterCommand {
+            header: ClientCommandHeader {
+                request_identifier: u64::MAX,
+                sector_idx,
+            },
+            content: ClientRegisterCommandContent::Read,
+        });
+        let system_cmd = RegisterCommand::System(SystemRegisterCommand {
+            header: SystemCommandHeader {
+                process_identifier: 1,
+                msg_ident: Uuid::new_v4(),
+                sector_idx,
+            },
+            content: SystemRegisterCommandContent::ReadProc,
+        });
+
+        // when
+        let client_frame = serialize(&client_cmd).await;
+        let system_frame = serialize(&system_cmd).await;
+
+        // then
+        assert_eq!(
+            u64::from_be_bytes(client_frame[16..24].try_into().unwrap()),
+            sector_idx
+        );
+        assert_eq!(
+            u64::from_be_bytes(system_frame[24..32].try_into().unwrap()),
+            sector_idx
+        );
+        assert_eq!(deserialized_sector_idx(&client_frame).await, sector_idx);
+        assert_eq!(deserialized_sector_idx(&system_frame).await, sector_idx);
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn process_with_many_sectors_serves_boundary_and_sparse_indices() {
+    // given
+    let config = TestProcessesConfig::new(3, 22510).with_n_sectors(LARGE_N_SECTORS);
+    config.start().await;
+    let mut stream = config.connect(0).await;
+    let written = boundary_sector_indices(LARGE_N_SECTORS);
+    let seed = rand::random();
+    let unwritten: Vec<u64> = sparse_sector_indices(seed, 32, LARGE_N_SECTORS)
+        .into_iter()
+        .filter(|idx| !written.contains(idx))
+        .collect();
+
+    // when
+    for (request_identifier, sector_idx) in written.iter().enumerate() {
+        let cmd = write_cmd(
+            request_identifier as u64,
+            *sector_idx,
+            fill_value(*sector_idx),
+        );
+        config
+            .send_cmd(&RegisterCommand::Client(cmd.clone()), &mut stream)
+            .await;
+        let response = config.read_response(&mut stream).await.unwrap();
+        config.assert_response_header(&response, &cmd);
+        assert!(matches!(response.header.status_code, StatusCode::Ok));
+    }
+
+    // then
+    for sector_idx in &written {
+        let data = read_sector(&config, &mut stream, *sector_idx).await;
+        assert_eq!(data, vec![fill_value(*sector_idx); 4096]);
+    }
+    for sector_idx in &unwritten {
+        let data = read_sector(&config, &mut stream, *sector_idx).await;
+        assert_eq!(
+            data,
+            vec![0; 4096],
+            "Unwritten sector {} is not zeroed (seed {})",
+            sector_idx,
+            seed
+        );
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn process_rejects_indices_past_the_end() {
+    // given
+    let config = TestProcessesConfig::new(3, 22520).with_n_sectors(LARGE_N_SECTORS);
+    config.start().await;
+    let mut stream = config.connect(1).await;
+
+    for (request_identifier, sector_idx) in invalid_sector_indices(LARGE_N_SECTORS)
+        .into_iter()
+        .enumerate()
+    {
+        // when
+        let cmd = write_cmd(request_identifier as u64, sector_idx, 1);
+        config
+            .send_cmd(&RegisterCommand::Client(cmd.clone()), &mut stream)
+            .await;
+        let response = config.read_response(&mut stream).await.unwrap();
+
+        // then
+        config.assert_response_header(&response, &cmd);
+        assert!(
+            matches!(response.header.status_code, StatusCode::InvalidSectorIndex),
+            "Write to sector {} was not rejected",
+            sector_idx
+        );
+        assert!(matches!(response.content, RegisterResponseContent::Write));
+    }
+
+    /* The process must stay usable afterwards */
+    assert_eq!(
+        read_sector(&config, &mut stream, LARGE_N_SECTORS - 1).await,
+        vec![0; 4096]
+    );
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn process_rejects_reads_past_the_end() {
+    // given
+    let config = TestProcessesConfig::new(3, 22530).with_n_sectors(LARGE_N_SECTORS);
+    config.start().await;
+
+    for (request_identifier, sector_idx) in invalid_sector_indices(LARGE_N_SECTORS)
+        .into_iter()
+        .enumerate()
+    {
+        /* Only the header is checked, so a fresh connection is used for
+         * every read, whether or not content follows it */
+        let mut stream = config.connect(2).await;
+
+        // when
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: request_identifier as u64,
+                        sector_idx,
+                    },
+                    content: ClientRegisterCommandContent::Read,
+                }),
+                &mut stream,
+            )
+            .await;
+        let mut header = [0; 16];
+        stream.read_exact(&mut header).await.unwrap();
+
+        // then
+        assert_eq!(&header[0..4], MAGIC_NUMBER.as_ref());
+        assert_eq!(
+            header[6],
+            StatusCode::InvalidSectorIndex as u8,
+            "Read of sector {} was not rejected",
+            sector_idx
+        );
+        assert_eq!(header[7], 0x41);
+        assert_eq!(
+            u64::from_be_bytes(header[8..16].try_into().unwrap()),
+            request_identifier as u64
+        );
+    }
+}
+
+/// Never zero, so that a written sector differs from an unwritten one.
+fn fill_value(sector_idx: u64) -> u8 {
+    (sector_idx % 255) as u8 + 1
+}
+
+fn write_cmd(request_identifier: u64, sector_idx: u64, value: u8) -> ClientRegisterCommand {
+    ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Write {
+            data: SectorVec(vec![value; 4096]),
+        },
+    }
+}
+
+async fn read_sector(
+    config: &TestProcessesConfig,
+    stream: &mut tokio::net::TcpStream,
+    sector_idx: u64,
+) -> Vec<u8> {
+    let cmd = ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier: sector_idx,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Read,
+    };
+    config
+        .send_cmd(&RegisterCommand::Client(cmd.clone()), stream)
+        .await;
+    let response = config.read_response(stream).await.unwrap();
+    config.assert_response_header(&response, &cmd);
+    assert!(matches!(response.header.status_code, StatusCode::Ok));
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(data)) => data,
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+}
+
+async fn serialize(cmd: &RegisterCommand) -> Vec<u8> {
+    let key = match cmd {
+        RegisterCommand::Client(_) => &[0x00_u8; 32][..],
+        RegisterCommand::System(_) => &[0x00_u8; 64][..],
+    };
+    let mut sink = Vec::new();
+    serialize_register_command(cmd, &mut sink, key)
+        .await
+        .expect("Could not serialize?");
+    sink
+}
+
+async fn deserialized_sector_idx(frame: &[u8]) -> u64 {
+    let mut slice: &[u8] = frame;
+    let data_read: &mut (dyn tokio::io::AsyncRead + Send + Unpin) = &mut slice;
+    let (cmd, hmac_valid) = deserialize_register_command(data_read, &[0x00_u8; 64], &[0x00_u8; 32])
+        .await
+        .expect("Could not deserialize");
+    assert!(hmac_valid);
+    match cmd {
+        RegisterCommand::Client(cmd) => cmd.header.sector_idx,
+        RegisterCommand::System(cmd) => cmd.header.sector_idx,
+    }
+}
@@ -0,0 +1,96 @@
+use assignment_2_solution::{build_sectors_manager, SectorVec};
+use assignment_2_test_utils::storage::{
+    CrashingWorkload, CrashingWorkloadConfig, StorageBudget, StorageFootprint,
+};
+use ntest::timeout;
+use tempfile::tempdir;
+
+#[tokio::test]
+#[timeout(60000)]
+async fn overwrites_do_not_grow_storage() {
+    // given
+    let root_drive_dir = tempdir().unwrap();
+    let mut workload = CrashingWorkload::new(
+        root_drive_dir.path().to_path_buf(),
+        CrashingWorkloadConfig {
+            seed: rand::random(),
+            writes: 2000,
+            sectors: 0..50,
+            crash_probability: 0.01,
+            torn_write_probability: 0.0,
+        },
+        StorageBudget::default(),
+    );
+
+    // when
+    workload.run().await;
+
+    // then
+    let footprint = StorageFootprint::of(root_drive_dir.path());
+    println!(
+        "{} sectors written, {} files, {} bytes, {} recoveries",
+        workload.distinct_sectors(),
+        footprint.files_count(),
+        footprint.total_bytes(),
+        workload.recoveries
+    );
+    workload.assert_within_budget();
+}
+
+#[tokio::test]
+#[timeout(60000)]
+async fn no_temporary_files_remain_after_torn_writes() {
+    // given
+    let root_drive_dir = tempdir().unwrap();
+    let mut workload = CrashingWorkload::new(
+        root_drive_dir.path().to_path_buf(),
+        CrashingWorkloadConfig {
+            seed: rand::random(),
+            writes: 300,
+            sectors: 0..20,
+            crash_probability: 0.05,
+            torn_write_probability: 0.2,
+        },
+        StorageBudget::default(),
+    );
+
+    // when
+    workload.run().await;
+
+    // then
+    assert!(workload.torn_writes > 0);
+    workload.assert_no_leftover_files().await;
+}
+
+#[tokio::test]
+#[timeout(30000)]
+async fn storage_is_proportional_to_distinct_sectors() {
+    // given
+    let root_drive_dir = tempdir().unwrap();
+    let sectors_manager = build_sectors_manager(root_drive_dir.path().to_path_buf()).await;
+    let budget = StorageBudget::default();
+
+    // when
+    /* Sparse indices must not make the storage grow with the largest one */
+    for (i, sector_idx) in (0..100u64).map(|i| i * 10_007).enumerate() {
+        sectors_manager
+            .write(sector_idx, &(SectorVec(vec![i as u8; 4096]), 1, 1))
+            .await;
+    }
+    let after_first_writes = StorageFootprint::of(root_drive_dir.path());
+    for round in 2..=10 {
+        for sector_idx in (0..100u64).map(|i| i * 10_007) {
+            sectors_manager
+                .write(sector_idx, &(SectorVec(vec![round; 4096]), round as u64, 1))
+                .await;
+        }
+    }
+
+    // then
+    budget.assert_within(root_drive_dir.path(), 100);
+    let after_overwrites = StorageFootprint::of(root_drive_dir.path());
+    assert_eq!(
+        after_overwrites.files_count(),
+        after_first_writes.files_count()
+    );
+}
@@ -3,6 +3,8 @@ use assignment_2_solution::{
     ClientRegisterCommandContent, Configuration, PublicConfiguration, RegisterCommand, SectorVec,
     MAGIC_NUMBER,
 };
+use assignment_2_test_utils::cluster_matrix::{cluster_shapes, ClusterSource};
+use assignment_2_test_utils::scenarios;
 use assignment_2_test_utils::system::*;
 use hmac::Mac;
 use ntest::timeout;
@@ -73,56 +75,9 @@ async fn single_process_system_completes_operations() {
 #[serial_test::serial]
 #[timeout(30000)]
 async fn concurrent_operations_on_the_same_sector() {
-    // given
-    let port_range_start = 21518;
-    let n_clients = 16;
-    let config = TestProcessesConfig::new(1, port_range_start);
-    config.start().await;
-    let mut streams = Vec::new();
-    for _ in 0..n_clients {
-        streams.push(config.connect(0).await);
-    }
-    // when
-    for (i, stream) in streams.iter_mut().enumerate() {
-        config
-            .send_cmd(
-                &RegisterCommand::Client(ClientRegisterCommand {
-                    header: ClientCommandHeader {
-                        request_identifier: i.try_into().unwrap(),
-                        sector_idx: 0,
-                    },
-                    content: ClientRegisterCommandContent::Write {
-                        data: SectorVec(vec![if i % 2 == 0 { 1 } else { 254 }; 4096]),
-                    },
-                }),
-                stream,
-            )
-            .await;
-    }
-
-    for stream in &mut streams {
-        config.read_response(stream).await.unwrap();
-    }
-
-    config
-        .send_cmd(
-            &RegisterCommand::Client(ClientRegisterCommand {
-                header: ClientCommandHeader {
-                    request_identifier: n_clients,
-                    sector_idx: 0,
-                },
-                content: ClientRegisterCommandContent::Read,
-            }),
-            &mut streams[0],
-        )
-        .await;
-    let response = config.read_response(&mut streams[0]).await.unwrap();
-
-    match response.content {
-        RegisterResponseContent::Read(SectorVec(sector)) => {
-            assert!(sector == vec![1; 4096] || sector == vec![254; 4096]);
-        }
-        _ => panic!("Expected read response"),
+    for shape in cluster_shapes(21518, 1) {
+        println!("{}", shape);
+        scenarios::concurrent_operations_on_the_same_sector(&ClusterSource::Linked, shape).await;
     }
 }
 
@@ -130,63 +85,11 @@ async fn concurrent_operations_on_the_same_sector() {
 #[serial_test::serial]
 #[timeout(40000)]
 async fn large_number_of_operations_execute_successfully() {
-    // given
-    let port_range_start = 21625;
-    let commands_total = 32;
-    let config = TestProcessesConfig::new(3, port_range_start);
-    config.start().await;
-    let mut stream = config.connect(2).await;
-
-    for cmd_idx in 0..commands_total {
-        config
-            .send_cmd(
-                &RegisterCommand::Client(ClientRegisterCommand {
-                    header: ClientCommandHeader {
-                        request_identifier: cmd_idx,
-                        sector_idx: cmd_idx,
-                    },
-                    content: ClientRegisterCommandContent::Write {
-                        data: SectorVec(vec![cmd_idx as u8; 4096]),
-                    },
-                }),
-                &mut stream,
-            )
-            .await;
-    }
-
-    for _ in 0..commands_total {
-        config.read_response(&mut stream).await.unwrap();
-    }
-
-    // when
-    for cmd_idx in 0..commands_total {
-        config
-            .send_cmd(
-                &RegisterCommand::Client(ClientRegisterCommand {
-                    header: ClientCommandHeader {
-                        request_identifier: cmd_idx + 256,
-                        sector_idx: cmd_idx,
-                    },
-                    content: ClientRegisterCommandContent::Read,
-                }),
-                &mut stream,
-            )
+    for shape in cluster_shapes(21625, 1) {
+        println!("{}", shape);
+        scenarios::large_number_of_operations_execute_successfully(&ClusterSource::Linked, shape)
             .await;
     }
-
-    // then
-    for _ in 0..commands_total {
-        let response = config.read_response(&mut stream).await.unwrap();
-        match response.content {
-            RegisterResponseContent::Read(SectorVec(sector)) => {
-                assert_eq!(
-                    sector,
-                    vec![(response.header.request_identifier - 256) as u8; 4096]
-                )
-            }
-            _ => panic!("Expected read response"),
-        }
-    }
 }
 
 async fn send_cmd(register_cmd: &RegisterCommand, stream: &mut TcpStream, hmac_client_key: &[u8]) {
@@ -0,0 +1,138 @@
+use assignment_2_test_utils::block_trace::*;
+use assignment_2_test_utils::system::TestProcessesConfig;
+use ntest::timeout;
+use std::sync::Arc;
+
+#[test]
+fn trace_requests_are_split_into_sectors() {
+    // given
+    let contents = "op,offset,length,timestamp\n\
+                    # comment\n\
+                    W,8192,16384,0.5\n\
+                    R,0,4096,1.25\n";
+
+    // when
+    let trace = parse_trace(contents).unwrap();
+
+    // then
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].kind, BlockOpKind::Write);
+    assert_eq!(trace[0].sectors(), 2..6);
+    assert_eq!(trace[1].kind, BlockOpKind::Read);
+    assert_eq!(trace[1].sectors(), 0..1);
+    assert_eq!(trace[1].timestamp, 1.25);
+}
+
+#[test]
+fn unaligned_requests_are_rejected() {
+    for line in [
+        "W,512,4096,0",
+        "R,0,1000,0",
+        "R,0,0,0",
+        "X,0,4096,0",
+        "R,0,4096",
+    ] {
+        assert!(parse_trace(line).is_err(), "{} was accepted", line);
+    }
+}
+
+#[test]
+fn synthetic_trace_rejects_too_small_drives() {
+    assert!(synthetic_trace(0, 10, MIN_SYNTHETIC_SECTORS - 1).is_err());
+    for seed in 0..32 {
+        let trace = synthetic_trace(seed, 100, MIN_SYNTHETIC_SECTORS).unwrap();
+        assert!(trace
+            .iter()
+            .all(|op| op.sectors().end <= MIN_SYNTHETIC_SECTORS));
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(5000)]
+async fn empty_trace_is_reported_without_latencies() {
+    // given
+    let config = Arc::new(TestProcessesConfig::new(1, 22940));
+    config.start().await;
+
+    // when
+    let report = replay_trace(config, &[], &ReplayConfig::default())
+        .await
+        .unwrap();
+
+    // then
+    assert_eq!(report.latency_percentile(50.0), None);
+    assert!(report.to_string().contains("p50 n/a"));
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn recorded_trace_replays_correctly() {
+    // given
+    let contents = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../test-utils/traces/mkfs.csv"
+    ))
+    .unwrap();
+    let trace = parse_trace(&contents).unwrap();
+    let config = Arc::new(TestProcessesConfig::new(3, 22910));
+    config.start().await;
+
+    // when
+    let report = replay_trace(config, &trace, &ReplayConfig::default())
+        .await
+        .unwrap();
+
+    // then
+    println!("{}", report);
+    assert_eq!(report.ops, trace.len());
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(60000)]
+async fn synthetic_trace_replays_correctly_with_deep_queue() {
+    // given
+    let seed = rand::random();
+    let trace = synthetic_trace(seed, 200, 1 << 14).unwrap();
+    let config = Arc::new(TestProcessesConfig::new(3, 22920));
+    config.start().await;
+    let replay = ReplayConfig {
+        queue_depth: 16,
+        connections_per_process: 2,
+        time_scale: None,
+    };
+
+    // when
+    let result = replay_trace(config, &trace, &replay).await;
+
+    // then
+    match result {
+        Ok(report) => {
+            println!("{}", report);
+            assert!(report.sector_commands >= trace.len());
+        }
+        Err(err) => panic!("Replay of trace with seed {} failed: {}", seed, err),
+    }
+}
+
+#[tokio::test]
+#[serial_test::serial]
+#[timeout(30000)]
+async fn trace_timestamps_are_honored() {
+    // given
+    let trace = parse_trace("W,0,4096,0\nW,4096,4096,0.5\nR,0,8192,1.0\n").unwrap();
+    let config = Arc::new(TestProcessesConfig::new(3, 22930));
+    config.start().await;
+    let replay = ReplayConfig {
+        time_scale: Some(1.0),
+        ..ReplayConfig::default()
+    };
+
+    // when
+    let report = replay_trace(config, &trace, &replay).await.unwrap();
+
+    // then
+    assert!(report.elapsed.as_secs_f64() >= 1.0);
+}
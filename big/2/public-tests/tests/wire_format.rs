@@ -0,0 +1,229 @@
+use assignment_2_solution::{
+    deserialize_register_command, ClientRegisterCommandContent, RegisterCommand, SectorVec,
+    SystemRegisterCommandContent,
+};
+use assignment_2_test_utils::golden::*;
+use assignment_2_test_utils::system::TestProcessesConfig;
+use ntest::timeout;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_client_read_matches_golden_frame() {
+    assert_command_matches_golden_frame("client_read").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_client_write_matches_golden_frame() {
+    assert_command_matches_golden_frame("client_write").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_read_proc_matches_golden_frame() {
+    assert_command_matches_golden_frame("system_read_proc").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_value_matches_golden_frame() {
+    assert_command_matches_golden_frame("system_value").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_write_proc_matches_golden_frame() {
+    assert_command_matches_golden_frame("system_write_proc").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn serialized_ack_matches_golden_frame() {
+    assert_command_matches_golden_frame("system_ack").await;
+}
+
+#[tokio::test]
+#[timeout(200)]
+async fn golden_command_frames_deserialize_with_valid_hmac() {
+    for frame in golden_frames() {
+        let GoldenContent::Command(expected) = &frame.content else {
+            continue;
+        };
+
+        // given
+        let golden = frame.load();
+        let mut slice: &[u8] = &golden[..];
+        let data_read: &mut (dyn tokio::io::AsyncRead + Send + Unpin) = &mut slice;
+
+        // when
+        let (deserialized_cmd, hmac_valid) = deserialize_register_command(
+            data_read,
+            &GOLDEN_HMAC_SYSTEM_KEY,
+            &GOLDEN_HMAC_CLIENT_KEY,
+        )
+        .await
+        .expect("Could not deserialize");
+
+        // then
+        assert!(hmac_valid, "{}: invalid hmac", frame.name);
+        assert_same_command(frame.name, expected, &deserialized_cmd);
+    }
+}
+
+#[tokio::test]
+#[timeout(5000)]
+async fn write_response_matches_golden_frame() {
+    // given
+    let (_config, mut stream) = start_golden_process(23410).await;
+
+    // when
+    let response = exchange_golden_frames(&mut stream, "client_write", "response_write").await;
+
+    // then
+    golden_frame("response_write").assert_matches(&response);
+}
+
+#[tokio::test]
+#[timeout(5000)]
+async fn read_response_matches_golden_frame() {
+    // given
+    let (_config, mut stream) = start_golden_process(23420).await;
+    exchange_golden_frames(&mut stream, "client_write", "response_write").await;
+
+    // when
+    let response = exchange_golden_frames(&mut stream, "client_read", "response_read").await;
+
+    // then
+    golden_frame("response_read").assert_matches(&response);
+}
+
+/// A single process with the golden keys and enough sectors for the golden
+/// sector index, so that it answers the golden requests with the golden
+/// responses.
+async fn start_golden_process(port: u16) -> (TestProcessesConfig, TcpStream) {
+    let config = TestProcessesConfig::new(1, port)
+        .with_hmac_keys(&GOLDEN_HMAC_SYSTEM_KEY, &GOLDEN_HMAC_CLIENT_KEY)
+        .with_n_sectors(u64::MAX);
+    config.start().await;
+    let stream = config.connect(0).await;
+    (config, stream)
+}
+
+/// Sends the golden request frame as is and returns the bytes the process
+/// answered with, up to the length of the golden response. Fewer bytes are
+/// returned if it stops sending, so that the comparison tells where.
+async fn exchange_golden_frames(stream: &mut TcpStream, request: &str, response: &str) -> Vec<u8> {
+    stream
+        .write_all(&golden_frame(request).load())
+        .await
+        .unwrap();
+
+    let expected_len = golden_frame(response).load().len();
+    let mut received = Vec::with_capacity(expected_len);
+    let mut buf = vec![0; expected_len];
+    while received.len() < expected_len {
+        let read = tokio::time::timeout(
+            Duration::from_secs(1),
+            stream.read(&mut buf[..expected_len - received.len()]),
+        )
+        .await;
+        match read {
+            Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+            Ok(Ok(read)) => received.extend_from_slice(&buf[..read]),
+        }
+    }
+    received
+}
+
+async fn assert_command_matches_golden_frame(name: &str) {
+    // given
+    let frame = golden_frame(name);
+
+    // when
+    let serialized = frame.serialize().await;
+
+    // then
+    frame.assert_matches(&serialized);
+}
+
+fn assert_same_command(name: &str, expected: &RegisterCommand, actual: &RegisterCommand) {
+    match (expected, actual) {
+        (RegisterCommand::Client(expected), RegisterCommand::Client(actual)) => {
+            assert_eq!(
+                expected.header.request_identifier, actual.header.request_identifier,
+                "{}",
+                name
+            );
+            assert_eq!(
+                expected.header.sector_idx, actual.header.sector_idx,
+                "{}",
+                name
+            );
+            match (&expected.content, &actual.content) {
+                (ClientRegisterCommandContent::Read, ClientRegisterCommandContent::Read) => {}
+                (
+                    ClientRegisterCommandContent::Write { data: expected },
+                    ClientRegisterCommandContent::Write { data: actual },
+                ) => assert_eq!(expected, actual, "{}", name),
+                _ => panic!("{}: wrong client command content", name),
+            }
+        }
+        (RegisterCommand::System(expected), RegisterCommand::System(actual)) => {
+            assert_eq!(
+                expected.header.process_identifier, actual.header.process_identifier,
+                "{}",
+                name
+            );
+            assert_eq!(
+                expected.header.msg_ident, actual.header.msg_ident,
+                "{}",
+                name
+            );
+            assert_eq!(
+                expected.header.sector_idx, actual.header.sector_idx,
+                "{}",
+                name
+            );
+            match (&expected.content, &actual.content) {
+                (
+                    SystemRegisterCommandContent::ReadProc,
+                    SystemRegisterCommandContent::ReadProc,
+                )
+                | (SystemRegisterCommandContent::Ack, SystemRegisterCommandContent::Ack) => {}
+                (
+                    SystemRegisterCommandContent::Value {
+                        timestamp,
+                        write_rank,
+                        sector_data: SectorVec(data),
+                    },
+                    SystemRegisterCommandContent::Value {
+                        timestamp: actual_timestamp,
+                        write_rank: actual_write_rank,
+                        sector_data: SectorVec(actual_data),
+                    },
+                )
+                | (
+                    SystemRegisterCommandContent::WriteProc {
+                        timestamp,
+                        write_rank,
+                        data_to_write: SectorVec(data),
+                    },
+                    SystemRegisterCommandContent::WriteProc {
+                        timestamp: actual_timestamp,
+                        write_rank: actual_write_rank,
+                        data_to_write: SectorVec(actual_data),
+                    },
+                ) => {
+                    assert_eq!(timestamp, actual_timestamp, "{}", name);
+                    assert_eq!(write_rank, actual_write_rank, "{}", name);
+                    assert_eq!(data, actual_data, "{}", name);
+                }
+                _ => panic!("{}: wrong system command content", name),
+            }
+        }
+        _ => panic!("{}: wrong command kind", name),
+    }
+}
@@ -0,0 +1,163 @@
+use crate::system::{RegisterResponseContent, TestProcessesConfig};
+use assignment_2_solution::{
+    serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+pub fn write_cmd(request_identifier: u64, sector_idx: u64, value: u8) -> RegisterCommand {
+    RegisterCommand::Client(ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Write {
+            data: SectorVec(vec![value; 4096]),
+        },
+    })
+}
+
+pub fn read_cmd(request_identifier: u64, sector_idx: u64) -> RegisterCommand {
+    RegisterCommand::Client(ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier,
+            sector_idx,
+        },
+        content: ClientRegisterCommandContent::Read,
+    })
+}
+
+async fn serialize(cmd: &RegisterCommand, hmac_client_key: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    serialize_register_command(cmd, &mut data, hmac_client_key)
+        .await
+        .unwrap();
+    data
+}
+
+/// Sends the first half of a Write and leaves the connection open.
+/// The returned stream must be kept alive for the client to keep hanging.
+pub async fn send_half_frame(config: &TestProcessesConfig, proc_idx: usize) -> TcpStream {
+    let data = serialize(&write_cmd(0, 0, 13), &config.hmac_client_key).await;
+    let mut stream = config.connect(proc_idx).await;
+    stream.write_all(&data[..data.len() / 2]).await.unwrap();
+    stream
+}
+
+/// Sends a whole command and closes the connection without reading the response.
+pub async fn send_and_disconnect(
+    config: &TestProcessesConfig,
+    proc_idx: usize,
+    cmd: &RegisterCommand,
+) {
+    let mut stream = config.connect(proc_idx).await;
+    config.send_cmd(cmd, &mut stream).await;
+}
+
+/// Pipelines reads and then consumes the responses one byte at a time,
+/// so that the process cannot flush them.
+pub struct SlowReader {
+    bytes_read: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl SlowReader {
+    pub async fn start(
+        config: &TestProcessesConfig,
+        proc_idx: usize,
+        sectors: std::ops::Range<u64>,
+        reads_count: u64,
+        byte_interval: Duration,
+    ) -> Self {
+        let mut stream = config.connect(proc_idx).await;
+        for request_identifier in 0..reads_count {
+            let sector_idx = sectors.start + request_identifier % (sectors.end - sectors.start);
+            config
+                .send_cmd(&read_cmd(request_identifier, sector_idx), &mut stream)
+                .await;
+        }
+
+        let bytes_read = Arc::new(AtomicUsize::new(0));
+        let task = {
+            let bytes_read = bytes_read.clone();
+            tokio::spawn(async move {
+                let mut byte = [0];
+                while stream.read_exact(&mut byte).await.is_ok() {
+                    bytes_read.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(byte_interval).await;
+                }
+            })
+        };
+
+        SlowReader { bytes_read, task }
+    }
+
+    pub fn bytes_read(&self) -> usize {
+        self.bytes_read.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for SlowReader {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Opens connections which never send anything. Both ends of every
+/// connection live in the test binary, so call `raise_open_files_limit`
+/// before opening more than a few hundred.
+pub async fn open_idle_connections(
+    config: &TestProcessesConfig,
+    proc_idx: usize,
+    count: usize,
+) -> Vec<TcpStream> {
+    let mut streams = Vec::with_capacity(count);
+    for _ in 0..count {
+        streams.push(config.connect(proc_idx).await);
+    }
+    streams
+}
+
+/// Raises the soft limit of open files to the hard limit, as the default
+/// 1024 is too low to hold thousands of connections.
+pub fn raise_open_files_limit() {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: both calls only access the struct passed by pointer
+    unsafe {
+        assert_eq!(libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit), 0);
+        limit.rlim_cur = limit.rlim_max;
+        assert_eq!(libc::setrlimit(libc::RLIMIT_NOFILE, &limit), 0);
+    }
+}
+
+/// Writes `value` to the sector and reads it back, checking the result.
+/// Returns the time both operations took.
+pub async fn timed_write_read(
+    config: &TestProcessesConfig,
+    stream: &mut TcpStream,
+    sector_idx: u64,
+    value: u8,
+) -> Duration {
+    let start = Instant::now();
+    config
+        .send_cmd(&write_cmd(1, sector_idx, value), stream)
+        .await;
+    config.read_response(stream).await.unwrap();
+    config.send_cmd(&read_cmd(2, sector_idx), stream).await;
+    let response = config.read_response(stream).await.unwrap();
+    let elapsed = start.elapsed();
+
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => assert_eq!(sector, vec![value; 4096]),
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+    elapsed
+}
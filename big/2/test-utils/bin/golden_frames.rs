@@ -0,0 +1,44 @@
+//! Regenerates or diffs the golden wire format frames in `test-utils/golden`.
+//!
+//! `cargo run --bin golden-frames` compares the frames produced by the linked
+//! solution with the checked-in ones and reports the first differing byte of
+//! each frame. `cargo run --bin golden-frames -- --write` overwrites the
+//! checked-in frames with the current output.
+use assignment_2_test_utils::golden::{golden_dir, golden_frames};
+
+#[tokio::main]
+async fn main() {
+    let write = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("--write") => true,
+        Some(arg) => {
+            eprintln!("Unknown argument: {}", arg);
+            eprintln!("Usage: golden-frames [--write]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut differences = 0;
+    for frame in golden_frames() {
+        let actual = frame.serialize().await;
+        if write {
+            std::fs::create_dir_all(golden_dir()).unwrap();
+            std::fs::write(frame.path(), &actual).unwrap();
+            println!("{}: written {} bytes", frame.name, actual.len());
+            continue;
+        }
+
+        let expected = std::fs::read(frame.path()).unwrap_or_default();
+        match frame.describe_difference(&expected, &actual) {
+            Some(difference) => {
+                differences += 1;
+                println!("{}", difference);
+            }
+            None => println!("{}: ok", frame.name),
+        }
+    }
+
+    if differences > 0 {
+        std::process::exit(1);
+    }
+}
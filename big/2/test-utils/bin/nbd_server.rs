@@ -0,0 +1,115 @@
+//! Network Block Device server in front of a register cluster, see
+//! `assignment_2_test_utils::nbd`.
+//!
+//! `cargo run --release --bin nbd-server -- ../simple_config` serves a
+//! cluster of `atomic_disc_drive` processes started with that config file,
+//! `cargo run --release --bin nbd-server -- --local 3` starts one in this
+//! process. The drive can then be used by any NBD client, for example
+//! `nbdinfo nbd://localhost/atomic_disc_drive` or, with the kernel module,
+//! `nbd-client localhost /dev/nbd0 -N atomic_disc_drive -b 4096`.
+use assignment_2_test_utils::external::read_config_file;
+use assignment_2_test_utils::nbd::{NbdServer, DEFAULT_EXPORT_NAME, NBD_DEFAULT_PORT};
+use assignment_2_test_utils::system::TestProcessesConfig;
+use std::path::Path;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: nbd-server (CONFIG | --local PROCESSES) [--port N] [--listen ADDR] \
+    [--rank N] [--export NAME]";
+
+#[tokio::main]
+async fn main() {
+    let _ = env_logger::builder().try_init();
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+
+    let cluster = match &args.cluster {
+        Cluster::ConfigFile(path) => read_config_file(Path::new(path)).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }),
+        Cluster::Local(processes) => {
+            let config = TestProcessesConfig::new(*processes, args.port);
+            config.start().await;
+            config
+        }
+    };
+    if args
+        .rank
+        .is_some_and(|rank| rank == 0 || rank > cluster.tcp_locations.len())
+    {
+        eprintln!("There is no rank {}", args.rank.unwrap());
+        std::process::exit(2);
+    }
+
+    let server = NbdServer::bind(
+        args.listen.as_str(),
+        Arc::new(cluster),
+        &args.export,
+        args.rank.map(|rank| rank - 1),
+    )
+    .await
+    .unwrap_or_else(|err| {
+        eprintln!("Could not listen on {}: {}", args.listen, err);
+        std::process::exit(1);
+    });
+    println!(
+        "nbd-server: serving {} ({} bytes) on {}",
+        args.export,
+        server.export_size(),
+        server.local_addr()
+    );
+    server.run().await;
+}
+
+enum Cluster {
+    ConfigFile(String),
+    Local(usize),
+}
+
+struct Args {
+    cluster: Cluster,
+    port: u16,
+    listen: String,
+    rank: Option<usize>,
+    export: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut cluster = None;
+    let mut parsed = Args {
+        cluster: Cluster::Local(0),
+        port: 25000,
+        listen: format!("127.0.0.1:{}", NBD_DEFAULT_PORT),
+        rank: None,
+        export: DEFAULT_EXPORT_NAME.to_string(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            cluster = Some(Cluster::ConfigFile(arg));
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of {}", arg))?;
+        match arg.as_str() {
+            "--local" => cluster = Some(Cluster::Local(parse_number(&value)?)),
+            "--port" => parsed.port = parse_number(&value)?,
+            "--listen" => parsed.listen = value,
+            "--rank" => parsed.rank = Some(parse_number(&value)?),
+            "--export" => parsed.export = value,
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    parsed.cluster = cluster.ok_or_else(|| "Missing cluster".to_string())?;
+    Ok(parsed)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Not a number: {}", value))
+}
@@ -0,0 +1,95 @@
+//! Runs a cluster under randomized client load and faults, checking that the
+//! operations stay linearizable.
+//!
+//! `cargo run --release --bin soak -- --duration 8h` keeps going for eight
+//! hours or until the first violation, which is written to a trace file
+//! together with the seed. Pass the seed back with `--seed` to re-run with the
+//! same random choices of operations and faults; their timing, and so the
+//! interleaving, may still differ.
+use assignment_2_test_utils::soak::{run_soak, SoakConfig, SoakFailure};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: soak [--duration 90s|30m|8h] [--seed N] [--processes N] \
+    [--clients N] [--sectors N] [--port N] [--fault-interval 5s] [--trace PATH]";
+
+#[tokio::main]
+async fn main() {
+    let _ = env_logger::builder().try_init();
+    let config = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+
+    println!(
+        "soak: seed {}, {:?}, {} processes, {} clients, {} sectors",
+        config.seed, config.duration, config.processes, config.clients, config.sectors
+    );
+    let seed = config.seed;
+    let trace_path = config.trace_path.clone();
+    match run_soak(config).await {
+        Ok(report) => println!(
+            "soak: ok, {} operations in {} segments checked, {} abandoned, {} faults",
+            report.ops_checked, report.segments_checked, report.abandoned, report.faults
+        ),
+        Err(failure) => {
+            let outcome = if matches!(failure, SoakFailure::Inconclusive(_)) {
+                "INCONCLUSIVE"
+            } else {
+                "FAILED"
+            };
+            println!("soak: {} with seed {}", outcome, seed);
+            println!("{}", failure);
+            println!("soak: trace written to {}", trace_path.display());
+            println!(
+                "soak: re-run with --seed {} for the same random choices",
+                seed
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn parse_args() -> Result<SoakConfig, String> {
+    let mut config = SoakConfig::new(rand::random(), Duration::from_secs(60));
+    let mut trace_path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of {}", arg))?;
+        match arg.as_str() {
+            "--duration" => config.duration = parse_duration(&value)?,
+            "--seed" => config.seed = parse_number(&value)?,
+            "--processes" => config.processes = parse_number(&value)?,
+            "--clients" => config.clients = parse_number(&value)?,
+            "--sectors" => config.sectors = parse_number(&value)?,
+            "--port" => config.port_range_start = parse_number(&value)?,
+            "--fault-interval" => config.fault_interval = parse_duration(&value)?,
+            "--trace" => trace_path = Some(PathBuf::from(value)),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    config.trace_path =
+        trace_path.unwrap_or_else(|| PathBuf::from(format!("soak-trace-{}.txt", config.seed)));
+    Ok(config)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Not a number: {}", value))
+}
+
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit) = value.split_at(value.trim_end_matches(char::is_alphabetic).len());
+    let number: u64 = parse_number(number)?;
+    let seconds = match unit {
+        "" | "s" => number,
+        "m" => number * 60,
+        "h" => number * 3600,
+        _ => return Err(format!("Unknown unit of duration: {}", value)),
+    };
+    Ok(Duration::from_secs(seconds))
+}
@@ -0,0 +1,110 @@
+//! Replays a block I/O trace against a cluster started in this process, see
+//! `assignment_2_test_utils::block_trace` for the trace format.
+//!
+//! `cargo run --release --bin trace-replay -- trace.csv` replays a recorded
+//! trace, `cargo run --release --bin trace-replay -- --synthetic 10000`
+//! a generated filesystem-like one.
+use assignment_2_test_utils::block_trace::{
+    parse_trace, replay_trace, synthetic_trace, ReplayConfig,
+};
+use assignment_2_test_utils::system::TestProcessesConfig;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: trace-replay (TRACE.csv | --synthetic OPS) [--seed N] \
+    [--processes N] [--port N] [--queue-depth N] [--connections N] [--time-scale F]";
+
+#[tokio::main]
+async fn main() {
+    let _ = env_logger::builder().try_init();
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+
+    let trace = match &args.source {
+        Source::File(path) => {
+            let contents = std::fs::read_to_string(path).unwrap_or_else(|err| {
+                eprintln!("Could not read {}: {}", path, err);
+                std::process::exit(2);
+            });
+            parse_trace(&contents).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            })
+        }
+        Source::Synthetic(ops) => synthetic_trace(args.seed, *ops, 1 << 16).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }),
+    };
+    let n_sectors = trace
+        .iter()
+        .map(|op| op.sectors().end)
+        .max()
+        .unwrap_or(1)
+        .max(TestProcessesConfig::N_SECTORS);
+
+    let config =
+        Arc::new(TestProcessesConfig::new(args.processes, args.port).with_n_sectors(n_sectors));
+    config.start().await;
+    match replay_trace(config, &trace, &args.replay).await {
+        Ok(report) => println!("trace-replay: ok, {}", report),
+        Err(err) => {
+            println!("trace-replay: FAILED: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+enum Source {
+    File(String),
+    Synthetic(usize),
+}
+
+struct Args {
+    source: Source,
+    seed: u64,
+    processes: usize,
+    port: u16,
+    replay: ReplayConfig,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut source = None;
+    let mut parsed = Args {
+        source: Source::Synthetic(0),
+        seed: rand::random(),
+        processes: 3,
+        port: 24000,
+        replay: ReplayConfig::default(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            source = Some(Source::File(arg));
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value of {}", arg))?;
+        match arg.as_str() {
+            "--synthetic" => source = Some(Source::Synthetic(parse_number(&value)?)),
+            "--seed" => parsed.seed = parse_number(&value)?,
+            "--processes" => parsed.processes = parse_number(&value)?,
+            "--port" => parsed.port = parse_number(&value)?,
+            "--queue-depth" => parsed.replay.queue_depth = parse_number(&value)?,
+            "--connections" => parsed.replay.connections_per_process = parse_number(&value)?,
+            "--time-scale" => parsed.replay.time_scale = Some(parse_number(&value)?),
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    parsed.source = source.ok_or_else(|| "Missing trace".to_string())?;
+    Ok(parsed)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Not a number: {}", value))
+}
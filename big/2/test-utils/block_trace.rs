@@ -0,0 +1,464 @@
+//! Replays block I/O traces against a cluster the way the Linux driver from
+//! the assignment would: every request is split into 4096-byte sector
+//! commands sent over several connections at once, with many requests in
+//! flight. Like a filesystem, the replay never has two overlapping requests
+//! in flight, which makes the expected result of every read exact.
+//!
+//! A trace is a CSV file with lines `op,offset,length,timestamp`, where `op`
+//! is `R` or `W`, `offset` and `length` are in bytes and multiples of 4096,
+//! and `timestamp` is in seconds since the start of the trace. Lines starting
+//! with `#` and a header line are skipped.
+use crate::system::{response_hmac_tag_is_ok, RegisterResponseContent, TestProcessesConfig};
+use assignment_2_solution::{
+    ClientCommandHeader, ClientRegisterCommand, ClientRegisterCommandContent, RegisterCommand,
+    SectorVec, StatusCode,
+};
+use futures::stream::{FuturesUnordered, StreamExt};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedReadHalf;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{Duration, Instant};
+
+pub const SECTOR_SIZE: u64 = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockOpKind {
+    Read,
+    Write,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockOp {
+    pub kind: BlockOpKind,
+    pub offset: u64,
+    pub length: u64,
+    pub timestamp: f64,
+}
+
+impl BlockOp {
+    pub fn sectors(&self) -> std::ops::Range<u64> {
+        self.offset / SECTOR_SIZE..(self.offset + self.length) / SECTOR_SIZE
+    }
+}
+
+pub fn parse_trace(contents: &str) -> Result<Vec<BlockOp>, String> {
+    let mut ops = Vec::new();
+    for (line_idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if line_idx == 0 && fields.first() == Some(&"op") {
+            continue;
+        }
+        let error = |what: &str| format!("Line {}: {}: {}", line_idx + 1, what, line);
+        let [kind, offset, length, timestamp] = fields[..] else {
+            return Err(error("expected 4 fields"));
+        };
+        let kind = match kind {
+            "R" | "r" | "read" => BlockOpKind::Read,
+            "W" | "w" | "write" => BlockOpKind::Write,
+            _ => return Err(error("unknown op")),
+        };
+        let offset: u64 = offset.parse().map_err(|_| error("invalid offset"))?;
+        let length: u64 = length.parse().map_err(|_| error("invalid length"))?;
+        let timestamp: f64 = timestamp.parse().map_err(|_| error("invalid timestamp"))?;
+        if !offset.is_multiple_of(SECTOR_SIZE) || !length.is_multiple_of(SECTOR_SIZE) || length == 0
+        {
+            return Err(error(
+                "offset and length must be positive multiples of 4096",
+            ));
+        }
+        ops.push(BlockOp {
+            kind,
+            offset,
+            length,
+            timestamp,
+        });
+    }
+    Ok(ops)
+}
+
+/// Smallest drive a synthetic trace fits on: 16 metadata sectors, room for
+/// a file of 32 sectors after them, and the journal in the last eighth.
+pub const MIN_SYNTHETIC_SECTORS: u64 = 55;
+
+/// Trace resembling a filesystem: sequential file writes and reads, small
+/// rewrites of a few hot metadata sectors and an appended journal.
+pub fn synthetic_trace(seed: u64, ops_count: usize, sectors: u64) -> Result<Vec<BlockOp>, String> {
+    if sectors < MIN_SYNTHETIC_SECTORS {
+        return Err(format!(
+            "A synthetic trace needs at least {} sectors, got {}",
+            MIN_SYNTHETIC_SECTORS, sectors
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let journal = sectors - sectors / 8..sectors;
+    let mut journal_head = journal.start;
+    let mut written_files: Vec<(u64, u64)> = Vec::new();
+    let mut ops = Vec::with_capacity(ops_count);
+    let mut timestamp = 0.0;
+
+    while ops.len() < ops_count {
+        timestamp += rng.gen_range(0.0..0.002);
+        let (kind, first_sector, sectors_count) = match rng.gen_range(0..10) {
+            0..=2 => {
+                let count = rng.gen_range(1..=32);
+                let first = rng.gen_range(16..journal.start - count);
+                written_files.push((first, count));
+                (BlockOpKind::Write, first, count)
+            }
+            3..=5 if !written_files.is_empty() => {
+                let (first, count) = written_files[rng.gen_range(0..written_files.len())];
+                (BlockOpKind::Read, first, count)
+            }
+            6 | 7 => (
+                if rng.gen_bool(0.5) {
+                    BlockOpKind::Read
+                } else {
+                    BlockOpKind::Write
+                },
+                rng.gen_range(0..16),
+                1,
+            ),
+            _ => {
+                let count = rng.gen_range(1..=4).min(journal.end - journal_head);
+                let first = journal_head;
+                journal_head += count;
+                if journal_head == journal.end {
+                    journal_head = journal.start;
+                }
+                (BlockOpKind::Write, first, count)
+            }
+        };
+        ops.push(BlockOp {
+            kind,
+            offset: first_sector * SECTOR_SIZE,
+            length: sectors_count * SECTOR_SIZE,
+            timestamp,
+        });
+    }
+    Ok(ops)
+}
+
+pub struct ReplayConfig {
+    /// Requests in flight at once, as the driver's queue depth.
+    pub queue_depth: usize,
+    pub connections_per_process: usize,
+    /// Requests are issued no earlier than their timestamp multiplied by
+    /// this. As fast as possible when `None`.
+    pub time_scale: Option<f64>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            queue_depth: 32,
+            connections_per_process: 2,
+            time_scale: None,
+        }
+    }
+}
+
+pub struct ReplayReport {
+    pub ops: usize,
+    pub sector_commands: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    /// Latencies of whole requests, sorted.
+    pub latencies: Vec<Duration>,
+}
+
+impl ReplayReport {
+    pub fn throughput_mib_s(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64()
+    }
+
+    /// `None` for an empty trace.
+    pub fn latency_percentile(&self, percentile: f64) -> Option<Duration> {
+        let last_idx = self.latencies.len().checked_sub(1)?;
+        let idx = (last_idx as f64 * percentile / 100.0).round() as usize;
+        Some(self.latencies[idx])
+    }
+}
+
+fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{:?}", latency),
+        None => "n/a".to_string(),
+    }
+}
+
+impl std::fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requests, {} sector commands, {} KiB in {:?} ({:.2} MiB/s), \
+             latency p50 {}, p99 {}, max {}",
+            self.ops,
+            self.sector_commands,
+            self.bytes / 1024,
+            self.elapsed,
+            self.throughput_mib_s(),
+            format_latency(self.latency_percentile(50.0)),
+            format_latency(self.latency_percentile(99.0)),
+            format_latency(self.latencies.last().copied())
+        )
+    }
+}
+
+/// Contents written to a sector by the op `op_idx` of a trace.
+pub fn sector_contents(op_idx: usize, sector_idx: u64) -> SectorVec {
+    /* Never all zeros, which is what unwritten sectors hold */
+    let fill = ((op_idx as u64 * 31 + sector_idx) % 255 + 1) as u8;
+    let mut data = vec![fill; SECTOR_SIZE as usize];
+    data[..8].copy_from_slice(&(op_idx as u64).to_be_bytes());
+    data[8..16].copy_from_slice(&sector_idx.to_be_bytes());
+    SectorVec(data)
+}
+
+type Reply = oneshot::Sender<Result<Option<SectorVec>, String>>;
+type Pending = Arc<Mutex<HashMap<u64, Reply>>>;
+
+/// Pipelines commands over one connection, matching responses to them by
+/// request identifier.
+struct Connection {
+    commands: mpsc::UnboundedSender<(ClientRegisterCommand, Reply)>,
+}
+
+impl Connection {
+    async fn open(config: Arc<TestProcessesConfig>, proc_idx: usize) -> Self {
+        let (read_half, mut write_half) = config.connect(proc_idx).await.into_split();
+        let pending: Pending = Arc::new(Mutex::new(HashMap::new()));
+        let (commands, mut commands_rx) =
+            mpsc::unbounded_channel::<(ClientRegisterCommand, Reply)>();
+
+        {
+            let pending = pending.clone();
+            let config = config.clone();
+            tokio::spawn(async move {
+                while let Some((cmd, reply)) = commands_rx.recv().await {
+                    pending
+                        .lock()
+                        .unwrap()
+                        .insert(cmd.header.request_identifier, reply);
+                    let mut data = Vec::new();
+                    assignment_2_solution::serialize_register_command(
+                        &RegisterCommand::Client(cmd),
+                        &mut data,
+                        &config.hmac_client_key,
+                    )
+                    .await
+                    .unwrap();
+                    if write_half.write_all(&data).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        tokio::spawn(receive_responses(config, read_half, pending));
+
+        Connection { commands }
+    }
+
+    async fn execute(&self, cmd: ClientRegisterCommand) -> Result<Option<SectorVec>, String> {
+        let (reply, response) = oneshot::channel();
+        self.commands
+            .send((cmd, reply))
+            .map_err(|_| "connection closed".to_string())?;
+        response
+            .await
+            .unwrap_or_else(|_| Err("connection closed".to_string()))
+    }
+}
+
+async fn receive_responses(
+    config: Arc<TestProcessesConfig>,
+    mut read_half: OwnedReadHalf,
+    pending: Pending,
+) {
+    loop {
+        let response = config.read_response(&mut read_half).await;
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                for (_, reply) in pending.lock().unwrap().drain() {
+                    let _ = reply.send(Err(err.clone()));
+                }
+                return;
+            }
+        };
+        let Some(reply) = pending
+            .lock()
+            .unwrap()
+            .remove(&response.header.request_identifier)
+        else {
+            continue;
+        };
+        let result = if !matches!(response.header.status_code, StatusCode::Ok) {
+            Err(format!("status {}", response.header.status_code as u8))
+        } else if !response_hmac_tag_is_ok(&response, &config.hmac_client_key) {
+            Err("invalid HMAC tag".to_string())
+        } else {
+            match response.content {
+                RegisterResponseContent::Read(sector) => Ok(Some(sector)),
+                RegisterResponseContent::Write => Ok(None),
+            }
+        };
+        let _ = reply.send(result);
+    }
+}
+
+/// Replays `trace` against the processes of `config`, which must be running,
+/// checking every read against a shadow copy of the drive.
+pub async fn replay_trace(
+    config: Arc<TestProcessesConfig>,
+    trace: &[BlockOp],
+    replay: &ReplayConfig,
+) -> Result<ReplayReport, String> {
+    let mut connections = Vec::new();
+    for _ in 0..replay.connections_per_process {
+        for proc_idx in 0..config.tcp_locations.len() {
+            connections.push(Connection::open(config.clone(), proc_idx).await);
+        }
+    }
+    let connections = Arc::new(connections);
+
+    let mut shadow: HashMap<u64, SectorVec> = HashMap::new();
+    let mut busy_sectors: HashSet<u64> = HashSet::new();
+    let mut in_flight = FuturesUnordered::new();
+    let mut next_request_identifier = 0;
+    let mut latencies = Vec::with_capacity(trace.len());
+    let mut sector_commands = 0;
+    let mut bytes = 0;
+    let start = Instant::now();
+
+    for (op_idx, op) in trace.iter().enumerate() {
+        while in_flight.len() >= replay.queue_depth
+            || op
+                .sectors()
+                .any(|sector_idx| busy_sectors.contains(&sector_idx))
+        {
+            let (done_idx, latency, results) = in_flight.next().await.unwrap();
+            latencies.push(latency);
+            finish(
+                done_idx,
+                &trace[done_idx],
+                results,
+                &mut shadow,
+                &mut busy_sectors,
+            )?;
+        }
+        if let Some(time_scale) = replay.time_scale {
+            tokio::time::sleep_until(start + Duration::from_secs_f64(op.timestamp * time_scale))
+                .await;
+        }
+
+        let mut commands = Vec::new();
+        for sector_idx in op.sectors() {
+            busy_sectors.insert(sector_idx);
+            let cmd = ClientRegisterCommand {
+                header: ClientCommandHeader {
+                    request_identifier: next_request_identifier,
+                    sector_idx,
+                },
+                content: match op.kind {
+                    BlockOpKind::Read => ClientRegisterCommandContent::Read,
+                    BlockOpKind::Write => ClientRegisterCommandContent::Write {
+                        data: sector_contents(op_idx, sector_idx),
+                    },
+                },
+            };
+            let connection = next_request_identifier as usize % connections.len();
+            next_request_identifier += 1;
+            commands.push((connection, cmd));
+        }
+        sector_commands += commands.len();
+        bytes += op.length;
+
+        let connections = connections.clone();
+        in_flight.push(async move {
+            let issued = Instant::now();
+            let results = futures::future::join_all(
+                commands
+                    .into_iter()
+                    .map(|(connection, cmd)| connections[connection].execute(cmd)),
+            )
+            .await;
+            (op_idx, issued.elapsed(), results)
+        });
+    }
+    while let Some((done_idx, latency, results)) = in_flight.next().await {
+        latencies.push(latency);
+        finish(
+            done_idx,
+            &trace[done_idx],
+            results,
+            &mut shadow,
+            &mut busy_sectors,
+        )?;
+    }
+
+    latencies.sort();
+    Ok(ReplayReport {
+        ops: trace.len(),
+        sector_commands,
+        bytes,
+        elapsed: start.elapsed(),
+        latencies,
+    })
+}
+
+fn finish(
+    op_idx: usize,
+    op: &BlockOp,
+    results: Vec<Result<Option<SectorVec>, String>>,
+    shadow: &mut HashMap<u64, SectorVec>,
+    busy_sectors: &mut HashSet<u64>,
+) -> Result<(), String> {
+    for (sector_idx, result) in op.sectors().zip(results) {
+        busy_sectors.remove(&sector_idx);
+        let result = result.map_err(|err| {
+            format!(
+                "Request {} ({:?} at offset {}) failed on sector {}: {}",
+                op_idx, op.kind, op.offset, sector_idx, err
+            )
+        })?;
+        match op.kind {
+            BlockOpKind::Write => {
+                shadow.insert(sector_idx, sector_contents(op_idx, sector_idx));
+            }
+            BlockOpKind::Read => {
+                let read = result.unwrap_or(SectorVec(vec![]));
+                let expected = shadow
+                    .get(&sector_idx)
+                    .cloned()
+                    .unwrap_or_else(|| SectorVec(vec![0; SECTOR_SIZE as usize]));
+                if read.0 != expected.0 {
+                    return Err(format!(
+                        "Request {} (read at offset {}) returned wrong data of sector {}, \
+                         expected the one written by request {}",
+                        op_idx,
+                        op.offset,
+                        sector_idx,
+                        written_by(&expected)
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn written_by(sector: &SectorVec) -> String {
+    if sector.0.iter().all(|byte| *byte == 0) {
+        "none".to_string()
+    } else {
+        u64::from_be_bytes(sector.0[..8].try_into().unwrap()).to_string()
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cluster_matrix::ClusterSource;
+use crate::restartable::RestartableProcesses;
+use crate::system::TestProcessesConfig;
+use assignment_2_solution::{serialize_register_command, RegisterCommand};
+use rand::Rng;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+/// Processes of a `TestProcessesConfig` which can be restarted with another
+/// client key, on the same storage directories. The system key never
+/// changes.
+pub struct KeyedCluster {
+    processes: RestartableProcesses,
+    config: TestProcessesConfig,
+}
+
+impl KeyedCluster {
+    /// Starts all processes with the client key of the returned `config()`.
+    pub async fn start(processes_count: usize, port_range_start: u16) -> Self {
+        let config = TestProcessesConfig::new(processes_count, port_range_start);
+        let processes = RestartableProcesses::new(
+            ClusterSource::Linked,
+            (0..processes_count)
+                .map(|proc_idx| config.config(proc_idx))
+                .collect(),
+        );
+        let mut cluster = KeyedCluster { processes, config };
+        for proc_idx in 0..processes_count {
+            let hmac_client_key = cluster.config.hmac_client_key.clone();
+            cluster.restart(proc_idx, &hmac_client_key).await;
+        }
+        cluster
+    }
+
+    pub fn config(&self) -> &TestProcessesConfig {
+        &self.config
+    }
+
+    /// The key the process currently verifies commands and signs responses
+    /// with.
+    pub fn client_key(&self, proc_idx: usize) -> &[u8] {
+        self.processes.client_key(proc_idx)
+    }
+
+    pub async fn kill(&mut self, proc_idx: usize) {
+        self.processes.kill(proc_idx).await;
+    }
+
+    /// Starts the process again with `hmac_client_key` and waits until it
+    /// accepts connections.
+    pub async fn restart(&mut self, proc_idx: usize, hmac_client_key: &[u8]) {
+        self.processes.restart(proc_idx, hmac_client_key).await;
+    }
+
+    /// Restarts every process, one by one, with `hmac_client_key`.
+    pub async fn rotate_client_key(&mut self, hmac_client_key: &[u8]) {
+        for proc_idx in 0..self.processes.len() {
+            self.restart(proc_idx, hmac_client_key).await;
+        }
+    }
+}
+
+pub fn random_client_key() -> Vec<u8> {
+    (0..32)
+        .map(|_| rand::thread_rng().gen_range(0..255))
+        .collect()
+}
+
+/// `TestProcessesConfig::send_cmd` for a client with its own key.
+pub async fn send_cmd_with_key(
+    register_cmd: &RegisterCommand,
+    stream: &mut TcpStream,
+    hmac_client_key: &[u8],
+) {
+    let mut data = Vec::new();
+    serialize_register_command(register_cmd, &mut data, hmac_client_key)
+        .await
+        .unwrap();
+
+    stream.write_all(&data).await.unwrap();
+}
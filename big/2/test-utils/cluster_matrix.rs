@@ -0,0 +1,145 @@
+//! Cluster sizes and client ranks the system tests are run with. Even sizes
+//! and large clusters compute majorities differently than the usual three
+//! processes do.
+//!
+//! `CLUSTER_SIZES=3,5 cargo test` limits the matrix to the given sizes.
+use crate::external::ExternalCluster;
+use crate::restartable::RestartableProcesses;
+use crate::system::TestProcessesConfig;
+use std::fmt;
+use std::path::PathBuf;
+
+pub const CLUSTER_SIZES: [usize; 5] = [1, 2, 3, 5, 7];
+pub const CLUSTER_SIZES_VAR: &str = "CLUSTER_SIZES";
+
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterShape {
+    pub processes_count: usize,
+    /// Index of the process clients of the test connect to.
+    pub client_proc_idx: usize,
+    pub port_range_start: u16,
+}
+
+impl ClusterShape {
+    pub fn config(&self) -> TestProcessesConfig {
+        TestProcessesConfig::new(self.processes_count, self.port_range_start)
+    }
+}
+
+/// Where the processes of a cluster come from.
+#[derive(Clone, Debug)]
+pub enum ClusterSource {
+    /// The solution linked with the tests, run within the test.
+    Linked,
+    /// `atomic_disc_drive` binaries, see `ExternalCluster`.
+    External(Vec<PathBuf>),
+}
+
+impl ClusterSource {
+    pub async fn start(&self, shape: &ClusterShape) -> TestCluster {
+        self.start_some(shape, 0..shape.processes_count).await
+    }
+
+    /// Starts only the given processes, so that the remaining ones can be
+    /// replaced by stubs.
+    pub async fn start_some(
+        &self,
+        shape: &ClusterShape,
+        proc_idxs: impl IntoIterator<Item = usize>,
+    ) -> TestCluster {
+        match self {
+            ClusterSource::Linked => {
+                let config = shape.config();
+                let mut processes = RestartableProcesses::new(
+                    ClusterSource::Linked,
+                    (0..shape.processes_count)
+                        .map(|proc_idx| config.config(proc_idx))
+                        .collect(),
+                );
+                for proc_idx in proc_idxs {
+                    processes.restart(proc_idx, &config.hmac_client_key).await;
+                }
+                TestCluster::Linked { processes, config }
+            }
+            ClusterSource::External(binaries) => TestCluster::External(
+                ExternalCluster::start_some(
+                    binaries,
+                    shape.processes_count,
+                    shape.port_range_start,
+                    proc_idxs,
+                )
+                .await,
+            ),
+        }
+    }
+}
+
+/// A running cluster of either source. Its processes, each on its own
+/// runtime or a child process, are stopped when it is dropped, and its
+/// storage directories are kept until then.
+pub enum TestCluster {
+    Linked {
+        processes: RestartableProcesses,
+        config: TestProcessesConfig,
+    },
+    External(ExternalCluster),
+}
+
+impl TestCluster {
+    pub fn config(&self) -> &TestProcessesConfig {
+        match self {
+            TestCluster::Linked { config, .. } => config,
+            TestCluster::External(cluster) => cluster.config(),
+        }
+    }
+}
+
+impl fmt::Display for ClusterShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} processes, clients connected to rank {}",
+            self.processes_count,
+            self.client_proc_idx + 1
+        )
+    }
+}
+
+pub fn cluster_sizes() -> Vec<usize> {
+    match std::env::var(CLUSTER_SIZES_VAR) {
+        Ok(sizes) => sizes
+            .split(',')
+            .map(|size| {
+                size.trim()
+                    .parse()
+                    .unwrap_or_else(|_| panic!("Invalid {}: {}", CLUSTER_SIZES_VAR, sizes))
+            })
+            .collect(),
+        Err(_) => CLUSTER_SIZES.to_vec(),
+    }
+}
+
+/// Every cluster size with at least `min_processes_count` processes, with
+/// clients connected to the first, the middle and the last rank. Clusters
+/// started one after another in a test still run, so every shape gets its
+/// own range of ports, starting at `port_range_start`.
+pub fn cluster_shapes(port_range_start: u16, min_processes_count: usize) -> Vec<ClusterShape> {
+    let mut shapes = Vec::new();
+    let mut port = port_range_start;
+    for processes_count in cluster_sizes() {
+        if processes_count < min_processes_count {
+            continue;
+        }
+        let mut client_proc_idxs = vec![0, processes_count / 2, processes_count - 1];
+        client_proc_idxs.dedup();
+        for client_proc_idx in client_proc_idxs {
+            shapes.push(ClusterShape {
+                processes_count,
+                client_proc_idx,
+                port_range_start: port,
+            });
+            port += processes_count as u16;
+        }
+    }
+    shapes
+}
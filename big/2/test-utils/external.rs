@@ -0,0 +1,175 @@
+use crate::cluster_matrix::ClusterSource;
+use crate::restartable::RestartableProcesses;
+use crate::system::TestProcessesConfig;
+use assignment_2_solution::Configuration;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use std::path::{Path, PathBuf};
+
+/// Colon separated paths of `atomic_disc_drive` binaries, see `interop.sh`.
+pub const BINARIES_VAR: &str = "ATOMIC_DISC_DRIVE_BINARIES";
+
+pub fn binaries_from_env() -> Vec<PathBuf> {
+    let binaries = std::env::var(BINARIES_VAR).unwrap_or_else(|_| {
+        panic!(
+            "Set {} to paths of atomic_disc_drive binaries, or use interop.sh",
+            BINARIES_VAR
+        )
+    });
+    binaries.split(':').map(PathBuf::from).collect()
+}
+
+/// Processes of a `TestProcessesConfig` run as separate `atomic_disc_drive`
+/// binaries, so that ranks of one cluster can come from different solutions.
+/// Rank `i + 1` runs `binaries[i % binaries.len()]`.
+pub struct ExternalCluster {
+    processes: RestartableProcesses,
+    config: TestProcessesConfig,
+}
+
+impl ExternalCluster {
+    pub async fn start(
+        binaries: &[PathBuf],
+        processes_count: usize,
+        port_range_start: u16,
+    ) -> Self {
+        Self::start_some(
+            binaries,
+            processes_count,
+            port_range_start,
+            0..processes_count,
+        )
+        .await
+    }
+
+    /// Starts only the given processes, so that the remaining ones can be
+    /// replaced by stubs.
+    pub async fn start_some(
+        binaries: &[PathBuf],
+        processes_count: usize,
+        port_range_start: u16,
+        proc_idxs: impl IntoIterator<Item = usize>,
+    ) -> Self {
+        let mut config = TestProcessesConfig::new(processes_count, port_range_start);
+        /* atomic_disc_drive reads the keys as lines of text */
+        config.hmac_system_key = ascii_key(64);
+        config.hmac_client_key = ascii_key(32);
+
+        let processes = RestartableProcesses::new(
+            ClusterSource::External(binaries.to_vec()),
+            (0..processes_count)
+                .map(|proc_idx| config.config(proc_idx))
+                .collect(),
+        );
+        let mut cluster = ExternalCluster { processes, config };
+        for proc_idx in proc_idxs {
+            cluster.restart(proc_idx).await;
+        }
+        cluster
+    }
+
+    pub fn config(&self) -> &TestProcessesConfig {
+        &self.config
+    }
+
+    pub fn binary(&self, proc_idx: usize) -> &Path {
+        self.processes.binary(proc_idx).unwrap()
+    }
+
+    pub async fn kill(&mut self, proc_idx: usize) {
+        self.processes.kill(proc_idx).await;
+    }
+
+    /// Starts the process again with the same storage directory and waits
+    /// until it accepts connections.
+    pub async fn restart(&mut self, proc_idx: usize) {
+        self.processes
+            .restart(proc_idx, &self.config.hmac_client_key)
+            .await;
+    }
+}
+
+/// Writes `config` in the format `atomic_disc_drive` reads: both keys as
+/// lines of text, the number of sectors, and a host line and a port line per
+/// process. The keys must be ASCII.
+pub fn write_config_file(config: &TestProcessesConfig, path: &Path) -> std::io::Result<()> {
+    let contents = config_file_contents(
+        &config.hmac_system_key,
+        &config.hmac_client_key,
+        config.n_sectors(),
+        &config.tcp_locations,
+    );
+    std::fs::write(path, contents)
+}
+
+/// `write_config_file` for the configuration of a single process, whose
+/// keys may differ from the ones of the others.
+pub fn write_process_config_file(config: &Configuration, path: &Path) -> std::io::Result<()> {
+    let contents = config_file_contents(
+        &config.hmac_system_key,
+        &config.hmac_client_key,
+        config.public.n_sectors,
+        &config.public.tcp_locations,
+    );
+    std::fs::write(path, contents)
+}
+
+fn config_file_contents(
+    hmac_system_key: &[u8],
+    hmac_client_key: &[u8],
+    n_sectors: u64,
+    tcp_locations: &[(String, u16)],
+) -> String {
+    let mut contents = String::new();
+    contents.push_str(std::str::from_utf8(hmac_system_key).unwrap());
+    contents.push('\n');
+    contents.push_str(std::str::from_utf8(hmac_client_key).unwrap());
+    contents.push('\n');
+    contents.push_str(&format!("{}\n", n_sectors));
+    for (host, port) in tcp_locations {
+        contents.push_str(&format!("{}\n{}\n", host, port));
+    }
+    contents
+}
+
+/// Reads a config file of `atomic_disc_drive`, see `write_config_file`.
+pub fn read_config_file(path: &Path) -> Result<TestProcessesConfig, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    let mut lines = contents.lines();
+    let mut next_line = |what: &str| {
+        lines
+            .next()
+            .ok_or_else(|| format!("{}: missing {}", path.display(), what))
+    };
+
+    let hmac_system_key = next_line("system key")?.as_bytes().to_vec();
+    let hmac_client_key = next_line("client key")?.as_bytes().to_vec();
+    if hmac_system_key.len() != 64 || hmac_client_key.len() != 32 {
+        return Err(format!("{}: invalid key length", path.display()));
+    }
+    let n_sectors = next_line("number of sectors")?
+        .parse()
+        .map_err(|_| format!("{}: invalid number of sectors", path.display()))?;
+
+    let mut tcp_locations = Vec::new();
+    while let Ok(host) = next_line("host") {
+        let port = next_line("port")?
+            .parse()
+            .map_err(|_| format!("{}: invalid port of {}", path.display(), host))?;
+        tcp_locations.push((host.to_string(), port));
+    }
+    Ok(TestProcessesConfig::of_running_cluster(
+        hmac_system_key,
+        hmac_client_key,
+        tcp_locations,
+        n_sectors,
+    ))
+}
+
+fn ascii_key(len: usize) -> Vec<u8> {
+    rand::thread_rng()
+        .sample_iter(Alphanumeric)
+        .take(len)
+        .collect()
+}
@@ -0,0 +1,269 @@
+use crate::system::{
+    encode_response, RegisterResponseContent, RegisterResponseHeader, HMAC_TAG_SIZE,
+};
+use assignment_2_solution::{
+    serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec, StatusCode, SystemCommandHeader,
+    SystemRegisterCommand, SystemRegisterCommandContent,
+};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub const GOLDEN_HMAC_CLIENT_KEY: [u8; 32] = [0x0c; 32];
+pub const GOLDEN_HMAC_SYSTEM_KEY: [u8; 64] = [0x5a; 64];
+
+/* Values are chosen so that every byte differs, which makes swapped fields
+ * and endianness mistakes show up at an exact offset */
+pub const GOLDEN_REQUEST_IDENTIFIER: u64 = 0x1122_3344_5566_7788;
+pub const GOLDEN_SECTOR_IDX: u64 = 0x0102_0304_0506_0708;
+pub const GOLDEN_PROCESS_IDENTIFIER: u8 = 0x03;
+pub const GOLDEN_MSG_IDENT: [u8; 16] = [
+    0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xab, 0xac, 0xad, 0xae, 0xaf,
+];
+pub const GOLDEN_TIMESTAMP: u64 = 0x0a0b_0c0d_0e0f_1011;
+pub const GOLDEN_WRITE_RANK: u8 = 0x07;
+
+pub fn golden_sector() -> SectorVec {
+    SectorVec((0..4096).map(|i| (i % 251) as u8).collect())
+}
+
+pub enum GoldenContent {
+    Command(RegisterCommand),
+    Response(RegisterResponseHeader, RegisterResponseContent),
+}
+
+pub struct GoldenFrame {
+    pub name: &'static str,
+    pub content: GoldenContent,
+    /// Names and lengths of consecutive fields of the frame, used to tell
+    /// which field a differing byte belongs to.
+    pub layout: Vec<(&'static str, usize)>,
+}
+
+impl GoldenFrame {
+    pub fn path(&self) -> PathBuf {
+        golden_dir().join(format!("{}.bin", self.name))
+    }
+
+    pub fn load(&self) -> Vec<u8> {
+        std::fs::read(self.path())
+            .unwrap_or_else(|err| panic!("Could not read {}: {}", self.path().display(), err))
+    }
+
+    pub fn hmac_key(&self) -> &'static [u8] {
+        match &self.content {
+            GoldenContent::Command(RegisterCommand::System(_)) => &GOLDEN_HMAC_SYSTEM_KEY,
+            _ => &GOLDEN_HMAC_CLIENT_KEY,
+        }
+    }
+
+    /// Commands are serialized with the solution, responses with the
+    /// test-utils encoder (the solution does not export one).
+    pub async fn serialize(&self) -> Vec<u8> {
+        match &self.content {
+            GoldenContent::Command(cmd) => {
+                let mut data = Vec::new();
+                serialize_register_command(cmd, &mut data, self.hmac_key())
+                    .await
+                    .unwrap();
+                data
+            }
+            GoldenContent::Response(header, content) => {
+                encode_response(header, content, self.hmac_key())
+            }
+        }
+    }
+
+    pub fn field_at(&self, offset: usize) -> &'static str {
+        let mut field_start = 0;
+        for (name, len) in &self.layout {
+            if offset < field_start + len {
+                return name;
+            }
+            field_start += len;
+        }
+        "<past the end of the frame>"
+    }
+
+    pub fn describe_difference(&self, expected: &[u8], actual: &[u8]) -> Option<String> {
+        let offset = expected
+            .iter()
+            .zip(actual.iter())
+            .position(|(e, a)| e != a)
+            .or_else(|| {
+                (expected.len() != actual.len()).then(|| expected.len().min(actual.len()))
+            })?;
+
+        Some(format!(
+            "{}: first difference at byte {} ({}): expected {}, got {} (frame length: expected {}, got {})",
+            self.name,
+            offset,
+            self.field_at(offset),
+            format_byte(expected.get(offset)),
+            format_byte(actual.get(offset)),
+            expected.len(),
+            actual.len(),
+        ))
+    }
+
+    pub fn assert_matches(&self, actual: &[u8]) {
+        if let Some(difference) = self.describe_difference(&self.load(), actual) {
+            panic!("{}", difference);
+        }
+    }
+}
+
+fn format_byte(byte: Option<&u8>) -> String {
+    match byte {
+        Some(byte) => format!("{:#04x}", byte),
+        None => "end of frame".to_string(),
+    }
+}
+
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+pub fn golden_frames() -> Vec<GoldenFrame> {
+    vec![
+        GoldenFrame {
+            name: "client_read",
+            content: GoldenContent::Command(client_cmd(ClientRegisterCommandContent::Read)),
+            layout: client_layout(false),
+        },
+        GoldenFrame {
+            name: "client_write",
+            content: GoldenContent::Command(client_cmd(ClientRegisterCommandContent::Write {
+                data: golden_sector(),
+            })),
+            layout: client_layout(true),
+        },
+        GoldenFrame {
+            name: "system_read_proc",
+            content: GoldenContent::Command(system_cmd(SystemRegisterCommandContent::ReadProc)),
+            layout: system_layout(false),
+        },
+        GoldenFrame {
+            name: "system_value",
+            content: GoldenContent::Command(system_cmd(SystemRegisterCommandContent::Value {
+                timestamp: GOLDEN_TIMESTAMP,
+                write_rank: GOLDEN_WRITE_RANK,
+                sector_data: golden_sector(),
+            })),
+            layout: system_layout(true),
+        },
+        GoldenFrame {
+            name: "system_write_proc",
+            content: GoldenContent::Command(system_cmd(SystemRegisterCommandContent::WriteProc {
+                timestamp: GOLDEN_TIMESTAMP,
+                write_rank: GOLDEN_WRITE_RANK,
+                data_to_write: golden_sector(),
+            })),
+            layout: system_layout(true),
+        },
+        GoldenFrame {
+            name: "system_ack",
+            content: GoldenContent::Command(system_cmd(SystemRegisterCommandContent::Ack)),
+            layout: system_layout(false),
+        },
+        GoldenFrame {
+            name: "response_read",
+            content: GoldenContent::Response(
+                response_header(),
+                RegisterResponseContent::Read(golden_sector()),
+            ),
+            layout: response_layout(true),
+        },
+        GoldenFrame {
+            name: "response_write",
+            content: GoldenContent::Response(response_header(), RegisterResponseContent::Write),
+            layout: response_layout(false),
+        },
+    ]
+}
+
+pub fn golden_frame(name: &str) -> GoldenFrame {
+    golden_frames()
+        .into_iter()
+        .find(|frame| frame.name == name)
+        .unwrap_or_else(|| panic!("No golden frame named {}", name))
+}
+
+fn client_cmd(content: ClientRegisterCommandContent) -> RegisterCommand {
+    RegisterCommand::Client(ClientRegisterCommand {
+        header: ClientCommandHeader {
+            request_identifier: GOLDEN_REQUEST_IDENTIFIER,
+            sector_idx: GOLDEN_SECTOR_IDX,
+        },
+        content,
+    })
+}
+
+fn system_cmd(content: SystemRegisterCommandContent) -> RegisterCommand {
+    RegisterCommand::System(SystemRegisterCommand {
+        header: SystemCommandHeader {
+            process_identifier: GOLDEN_PROCESS_IDENTIFIER,
+            msg_ident: Uuid::from_bytes(GOLDEN_MSG_IDENT),
+            sector_idx: GOLDEN_SECTOR_IDX,
+        },
+        content,
+    })
+}
+
+fn response_header() -> RegisterResponseHeader {
+    RegisterResponseHeader {
+        status_code: StatusCode::Ok,
+        request_identifier: GOLDEN_REQUEST_IDENTIFIER,
+    }
+}
+
+fn client_layout(with_data: bool) -> Vec<(&'static str, usize)> {
+    let mut layout = vec![
+        ("magic number", 4),
+        ("padding", 3),
+        ("message type", 1),
+        ("request identifier", 8),
+        ("sector index", 8),
+    ];
+    if with_data {
+        layout.push(("sector data", 4096));
+    }
+    layout.push(("hmac tag", HMAC_TAG_SIZE));
+    layout
+}
+
+fn system_layout(with_data: bool) -> Vec<(&'static str, usize)> {
+    let mut layout = vec![
+        ("magic number", 4),
+        ("padding", 2),
+        ("process identifier", 1),
+        ("message type", 1),
+        ("message identifier", 16),
+        ("sector index", 8),
+    ];
+    if with_data {
+        layout.extend([
+            ("timestamp", 8),
+            ("padding", 7),
+            ("write rank", 1),
+            ("sector data", 4096),
+        ]);
+    }
+    layout.push(("hmac tag", HMAC_TAG_SIZE));
+    layout
+}
+
+fn response_layout(with_data: bool) -> Vec<(&'static str, usize)> {
+    let mut layout = vec![
+        ("magic number", 4),
+        ("padding", 2),
+        ("status code", 1),
+        ("message type", 1),
+        ("request identifier", 8),
+    ];
+    if with_data {
+        layout.push(("sector data", 4096));
+    }
+    layout.push(("hmac tag", HMAC_TAG_SIZE));
+    layout
+}
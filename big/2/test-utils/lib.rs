@@ -1,5 +1,21 @@
 pub mod atomic_register;
+pub mod golden;
 pub mod system;
 pub mod transfer;
 pub mod mikolajkowe;
 pub mod reconnect;
+pub mod lossy_peer;
+pub mod relay;
+pub mod abusive_client;
+pub mod storage;
+pub mod sector_indices;
+pub mod resources;
+pub mod linearizability;
+pub mod soak;
+pub mod external;
+pub mod block_trace;
+pub mod nbd;
+pub mod cluster_matrix;
+pub mod client_keys;
+pub mod scenarios;
+pub mod restartable;
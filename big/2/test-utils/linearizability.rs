@@ -0,0 +1,398 @@
+//! Streaming linearizability checker for a set of atomic registers, one per
+//! sector.
+//!
+//! Every write must store a value no other write stores, which makes a read
+//! tell exactly which write it observed. The history of each sector is cut
+//! into segments at the moments when no operation on it is in flight. Each
+//! segment is checked on its own, carrying over the set of register states
+//! it could have ended in, so memory and time do not grow with the length of
+//! the run.
+//!
+//! An operation whose outcome is unknown, e.g. because the connection broke
+//! when the process was restarted, is `abandon`ed. An abandoned write may
+//! take effect at any later moment, but at most once. It is forgotten after
+//! `pending_lifetime`, after which its value showing up is a violation.
+//! Only `MAX_PENDING_WRITES` of them fit in a single segment, the check of a
+//! sector with more is inconclusive.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Identifies the value stored by a write, 0 being the value of a sector
+/// that was never written.
+pub type ValueId = u64;
+
+pub const INITIAL_VALUE: ValueId = 0;
+
+/// Abandoned writes which may still take effect, per sector.
+const MAX_PENDING_WRITES: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct OpId(u64);
+
+#[derive(Clone, Copy, Debug)]
+pub enum OpKind {
+    Read(Option<ValueId>),
+    Write(ValueId),
+}
+
+#[derive(Clone, Debug)]
+pub struct Op {
+    pub id: OpId,
+    pub client: usize,
+    pub sector_idx: u64,
+    pub invoke: u64,
+    /// `None` for abandoned operations.
+    pub response: Option<u64>,
+    pub kind: OpKind,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let response = match self.response {
+            Some(response) => response.to_string(),
+            None => "?".to_string(),
+        };
+        let kind = match self.kind {
+            OpKind::Read(Some(value)) => format!("read -> {:#x}", value),
+            OpKind::Read(None) => "read -> ?".to_string(),
+            OpKind::Write(value) => format!("write {:#x}", value),
+        };
+        write!(
+            f,
+            "[{}, {}] client {} sector {}: {}",
+            self.invoke, response, self.client, self.sector_idx, kind
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Violation {
+    pub sector_idx: u64,
+    /// Operations of the segment which could not be linearized.
+    pub segment: Vec<Op>,
+    /// Values the register could hold when the segment started.
+    pub possible_values: Vec<ValueId>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Operations on sector {} are not linearizable",
+            self.sector_idx
+        )?;
+        let values: Vec<String> = self
+            .possible_values
+            .iter()
+            .map(|value| format!("{:#x}", value))
+            .collect();
+        writeln!(f, "values possible before: {}", values.join(", "))?;
+        for op in &self.segment {
+            writeln!(f, "  {}", op)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a history was not shown to be linearizable.
+#[derive(Debug)]
+pub enum CheckError {
+    NotLinearizable(Violation),
+    /// The checker gave up on a sector, which says nothing about the solution
+    Inconclusive {
+        sector_idx: u64,
+        reason: String,
+    },
+}
+
+impl CheckError {
+    pub fn sector_idx(&self) -> u64 {
+        match self {
+            CheckError::NotLinearizable(violation) => violation.sector_idx,
+            CheckError::Inconclusive { sector_idx, .. } => *sector_idx,
+        }
+    }
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::NotLinearizable(violation) => write!(f, "{}", violation),
+            CheckError::Inconclusive { sector_idx, reason } => writeln!(
+                f,
+                "Operations on sector {} could not be checked: {}",
+                sector_idx, reason
+            ),
+        }
+    }
+}
+
+/// State of a register: its value and the set of pending writes, by slot,
+/// which have already taken effect.
+type State = (ValueId, u64);
+
+struct PendingWrite {
+    value: ValueId,
+    abandoned_at: Instant,
+    /// Still part of the open segment, which decides where it may take effect
+    in_segment: bool,
+}
+
+struct SectorHistory {
+    states: HashSet<State>,
+    pending: Vec<Option<PendingWrite>>,
+    in_flight: HashMap<OpId, Op>,
+    segment: Vec<Op>,
+}
+
+impl SectorHistory {
+    fn new() -> Self {
+        SectorHistory {
+            states: HashSet::from([(INITIAL_VALUE, 0)]),
+            pending: (0..MAX_PENDING_WRITES).map(|_| None).collect(),
+            in_flight: HashMap::new(),
+            segment: Vec::new(),
+        }
+    }
+}
+
+pub struct LinearizabilityChecker {
+    sectors: HashMap<u64, SectorHistory>,
+    clock: u64,
+    next_op_id: u64,
+    pending_lifetime: Duration,
+    pub segments_checked: u64,
+    pub ops_checked: u64,
+}
+
+impl LinearizabilityChecker {
+    pub fn new(pending_lifetime: Duration) -> Self {
+        LinearizabilityChecker {
+            sectors: HashMap::new(),
+            clock: 0,
+            next_op_id: 0,
+            pending_lifetime,
+            segments_checked: 0,
+            ops_checked: 0,
+        }
+    }
+
+    /// Records the start of an operation. `write` is the value written, or
+    /// `None` for a read.
+    pub fn invoke(&mut self, client: usize, sector_idx: u64, write: Option<ValueId>) -> OpId {
+        let id = OpId(self.next_op_id);
+        self.next_op_id += 1;
+        self.clock += 1;
+        let op = Op {
+            id,
+            client,
+            sector_idx,
+            invoke: self.clock,
+            response: None,
+            kind: match write {
+                Some(value) => OpKind::Write(value),
+                None => OpKind::Read(None),
+            },
+        };
+        self.sectors
+            .entry(sector_idx)
+            .or_insert_with(SectorHistory::new)
+            .in_flight
+            .insert(id, op);
+        id
+    }
+
+    /// Records the end of an operation, with the value observed by a read.
+    pub fn complete(
+        &mut self,
+        sector_idx: u64,
+        id: OpId,
+        read: Option<ValueId>,
+    ) -> Result<(), CheckError> {
+        self.clock += 1;
+        let history = self.sectors.get_mut(&sector_idx).unwrap();
+        let mut op = history.in_flight.remove(&id).unwrap();
+        op.response = Some(self.clock);
+        if let OpKind::Read(_) = op.kind {
+            op.kind = OpKind::Read(read);
+        }
+        history.segment.push(op);
+        self.close_segment_if_idle(sector_idx)
+    }
+
+    /// Records that the outcome of an operation will never be known.
+    pub fn abandon(&mut self, sector_idx: u64, id: OpId) -> Result<(), CheckError> {
+        let history = self.sectors.get_mut(&sector_idx).unwrap();
+        let op = history.in_flight.remove(&id).unwrap();
+        if let OpKind::Write(value) = op.kind {
+            let slot = match history.pending.iter().position(Option::is_none) {
+                Some(slot) => slot,
+                None => {
+                    let Some(oldest) = oldest_pending(&history.pending) else {
+                        return Err(CheckError::Inconclusive {
+                            sector_idx,
+                            reason: format!(
+                                "more than {} abandoned writes in a single segment",
+                                MAX_PENDING_WRITES
+                            ),
+                        });
+                    };
+                    forget_pending(history, oldest);
+                    oldest
+                }
+            };
+            history.pending[slot] = Some(PendingWrite {
+                value,
+                abandoned_at: Instant::now(),
+                in_segment: true,
+            });
+            history.segment.push(op);
+        }
+        self.close_segment_if_idle(sector_idx)
+    }
+
+    fn close_segment_if_idle(&mut self, sector_idx: u64) -> Result<(), CheckError> {
+        let history = self.sectors.get_mut(&sector_idx).unwrap();
+        if !history.in_flight.is_empty() {
+            return Ok(());
+        }
+
+        let segment = std::mem::take(&mut history.segment);
+        let states = check_segment(&history.states, &history.pending, &segment);
+        if states.is_empty() {
+            let mut possible_values: Vec<ValueId> =
+                history.states.iter().map(|(value, _)| *value).collect();
+            possible_values.sort_unstable();
+            possible_values.dedup();
+            return Err(CheckError::NotLinearizable(Violation {
+                sector_idx,
+                segment,
+                possible_values,
+            }));
+        }
+        history.states = states;
+        self.segments_checked += 1;
+        self.ops_checked += segment.len() as u64;
+
+        for pending in history.pending.iter_mut().flatten() {
+            pending.in_segment = false;
+        }
+        let expired: Vec<usize> = history
+            .pending
+            .iter()
+            .enumerate()
+            .filter(|(_, pending)| {
+                pending
+                    .as_ref()
+                    .is_some_and(|pending| pending.abandoned_at.elapsed() > self.pending_lifetime)
+            })
+            .map(|(slot, _)| slot)
+            .collect();
+        for slot in expired {
+            forget_pending(history, slot);
+        }
+        Ok(())
+    }
+}
+
+/// `None` if all pending writes belong to the open segment, which still
+/// needs them.
+fn oldest_pending(pending: &[Option<PendingWrite>]) -> Option<usize> {
+    pending
+        .iter()
+        .enumerate()
+        .filter(|(_, pending)| pending.as_ref().is_some_and(|pending| !pending.in_segment))
+        .min_by_key(|(_, pending)| pending.as_ref().unwrap().abandoned_at)
+        .map(|(slot, _)| slot)
+}
+
+fn forget_pending(history: &mut SectorHistory, slot: usize) {
+    history.pending[slot] = None;
+    history.states = history
+        .states
+        .iter()
+        .map(|(value, mask)| (*value, mask & !(1 << slot)))
+        .collect();
+}
+
+/// Returns the states the register may be in after the segment, starting in
+/// any of `states`. Empty if the segment cannot be linearized.
+fn check_segment(
+    states: &HashSet<State>,
+    pending: &[Option<PendingWrite>],
+    segment: &[Op],
+) -> HashSet<State> {
+    /* Abandoned writes of this segment may only take effect after their
+     * invocation, so they are ordered like the other ops, except that they
+     * never have to be linearized */
+    let slot_of_value: HashMap<ValueId, usize> = pending
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, pending)| {
+            pending
+                .as_ref()
+                .filter(|pending| pending.in_segment)
+                .map(|pending| (pending.value, slot))
+        })
+        .collect();
+    let earlier_pending: Vec<(usize, ValueId)> = pending
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, pending)| {
+            pending
+                .as_ref()
+                .filter(|pending| !pending.in_segment)
+                .map(|pending| (slot, pending.value))
+        })
+        .collect();
+
+    let words = segment.len().div_ceil(64);
+    let mut results = HashSet::new();
+    let mut visited: HashSet<(Vec<u64>, State)> = HashSet::new();
+    let mut stack: Vec<(Vec<u64>, State)> = states
+        .iter()
+        .map(|state| (vec![0; words], *state))
+        .collect();
+
+    while let Some((done, state)) = stack.pop() {
+        if !visited.insert((done.clone(), state)) {
+            continue;
+        }
+        let is_done = |idx: usize| done[idx / 64] & (1 << (idx % 64)) != 0;
+
+        let first_response = segment
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !is_done(*idx))
+            .filter_map(|(_, op)| op.response)
+            .min();
+        let Some(first_response) = first_response else {
+            /* Only abandoned writes are left, they may take effect later */
+            results.insert(state);
+            continue;
+        };
+
+        let (value, mask) = state;
+        for (idx, op) in segment.iter().enumerate() {
+            if is_done(idx) || op.invoke > first_response {
+                continue;
+            }
+            let next_state = match (op.kind, op.response) {
+                (OpKind::Read(Some(read)), _) if read == value => state,
+                (OpKind::Read(_), _) => continue,
+                (OpKind::Write(written), Some(_)) => (written, mask),
+                (OpKind::Write(written), None) => (written, mask | (1 << slot_of_value[&written])),
+            };
+            let mut next_done = done.clone();
+            next_done[idx / 64] |= 1 << (idx % 64);
+            stack.push((next_done, next_state));
+        }
+        for (slot, written) in &earlier_pending {
+            if mask & (1 << slot) == 0 {
+                stack.push((done.clone(), (*written, mask | (1 << slot))));
+            }
+        }
+    }
+    results
+}
@@ -0,0 +1,295 @@
+use crate::system::TestProcessesConfig;
+use assignment_2_solution::{
+    deserialize_register_command, serialize_register_command, RegisterCommand, SectorVec,
+    SystemCommandHeader, SystemRegisterCommand, SystemRegisterCommandContent,
+};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{JoinHandle, JoinSet};
+use uuid::Uuid;
+
+/// Pause after a failed `accept`, as the error usually persists for a while.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Copies of a single system message (identified by its `msg_ident` and
+/// message type) received by a `LossyPeer`.
+#[derive(Clone, Debug)]
+pub struct ReceivedCopies {
+    pub sender: u8,
+    pub sector_idx: u64,
+    pub count: usize,
+    pub first_received: Instant,
+    pub answered: Option<Instant>,
+}
+
+impl ReceivedCopies {
+    /// Time between the first (discarded) copy and the copy that got answered.
+    pub fn retransmission_delay(&self) -> Option<Duration> {
+        self.answered
+            .map(|answered| answered.duration_since(self.first_received))
+    }
+}
+
+/// A stub rank that takes the place of a real process in the system. It
+/// discards the first `discarded_copies` copies of every ReadProc and
+/// WriteProc it receives, and answers later copies like a correct register
+/// would. Real processes can only complete operations needing this rank if
+/// they retransmit. Answers for chosen sectors can also be delayed, to keep
+/// operations on them pending.
+pub struct LossyPeer {
+    discarded_copies: usize,
+    received: Arc<Mutex<HashMap<(Uuid, u8), ReceivedCopies>>>,
+    sector_delays: Arc<Mutex<HashMap<u64, Duration>>>,
+    accept_task: JoinHandle<()>,
+}
+
+struct StubState {
+    self_rank: u8,
+    discarded_copies: usize,
+    hmac_system_key: [u8; 64],
+    hmac_client_key: [u8; 32],
+    tcp_locations: Vec<(String, u16)>,
+    sectors: tokio::sync::Mutex<HashMap<u64, (u64, u8, SectorVec)>>,
+    sector_delays: Arc<Mutex<HashMap<u64, Duration>>>,
+    outgoing: tokio::sync::Mutex<HashMap<u8, TcpStream>>,
+    received: Arc<Mutex<HashMap<(Uuid, u8), ReceivedCopies>>>,
+}
+
+impl LossyPeer {
+    pub async fn start(
+        config: &TestProcessesConfig,
+        proc_idx: usize,
+        discarded_copies: usize,
+    ) -> LossyPeer {
+        let location = config.tcp_locations.get(proc_idx).unwrap();
+        let listener = TcpListener::bind((location.0.as_str(), location.1))
+            .await
+            .expect("Could not bind stub rank");
+        let received = Arc::new(Mutex::new(HashMap::new()));
+        let sector_delays = Arc::new(Mutex::new(HashMap::new()));
+        let state = Arc::new(StubState {
+            self_rank: (proc_idx + 1) as u8,
+            discarded_copies,
+            hmac_system_key: config.hmac_system_key.clone().try_into().unwrap(),
+            hmac_client_key: config.hmac_client_key.clone().try_into().unwrap(),
+            tcp_locations: config.tcp_locations.clone(),
+            sectors: tokio::sync::Mutex::new(HashMap::new()),
+            sector_delays: sector_delays.clone(),
+            outgoing: tokio::sync::Mutex::new(HashMap::new()),
+            received: received.clone(),
+        });
+
+        let accept_task = tokio::spawn(async move {
+            // Dropping the set (when the accept task is aborted) aborts
+            // all connection tasks as well
+            let mut connections = JoinSet::new();
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(err) => {
+                        // E.g. out of file descriptors, retrying at once
+                        // would spin
+                        log::warn!("Lossy peer could not accept a connection: {}", err);
+                        tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                        continue;
+                    }
+                };
+                connections.spawn(handle_connection(stream, state.clone()));
+            }
+        });
+
+        LossyPeer {
+            discarded_copies,
+            received,
+            sector_delays,
+            accept_task,
+        }
+    }
+
+    /// Answers to messages about the sector are sent only after `delay`.
+    pub fn delay_sector(&self, sector_idx: u64, delay: Duration) {
+        self.sector_delays.lock().unwrap().insert(sector_idx, delay);
+    }
+
+    pub fn received(&self) -> Vec<ReceivedCopies> {
+        self.received.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn discarded_total(&self) -> usize {
+        self.received()
+            .iter()
+            .map(|copies| copies.count.min(self.discarded_copies))
+            .sum()
+    }
+
+    pub fn answered_total(&self) -> usize {
+        self.received()
+            .iter()
+            .filter(|copies| copies.answered.is_some())
+            .count()
+    }
+
+    pub fn retransmission_delays(&self) -> Vec<Duration> {
+        self.received()
+            .iter()
+            .filter_map(ReceivedCopies::retransmission_delay)
+            .collect()
+    }
+}
+
+impl Drop for LossyPeer {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: Arc<StubState>) {
+    let mut delayed_answers = JoinSet::new();
+    loop {
+        let Ok((cmd, hmac_valid)) = deserialize_register_command(
+            &mut stream,
+            &state.hmac_system_key,
+            &state.hmac_client_key,
+        )
+        .await
+        else {
+            return;
+        };
+        let RegisterCommand::System(cmd) = cmd else {
+            continue;
+        };
+        if !hmac_valid {
+            continue;
+        }
+
+        let msg_type = match cmd.content {
+            SystemRegisterCommandContent::ReadProc => 0x03,
+            SystemRegisterCommandContent::WriteProc { .. } => 0x05,
+            // The stub never starts operations, so answers are not expected
+            SystemRegisterCommandContent::Value { .. } | SystemRegisterCommandContent::Ack => {
+                continue
+            }
+        };
+
+        if !state.record_copy(&cmd, msg_type) {
+            continue;
+        }
+        let delay = state
+            .sector_delays
+            .lock()
+            .unwrap()
+            .get(&cmd.header.sector_idx)
+            .copied();
+        match delay {
+            Some(delay) => {
+                let state = state.clone();
+                delayed_answers.spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    state.answer_and_send(cmd).await;
+                });
+            }
+            None => state.answer_and_send(cmd).await,
+        }
+    }
+}
+
+impl StubState {
+    /// Returns whether the copy should be answered.
+    fn record_copy(&self, cmd: &SystemRegisterCommand, msg_type: u8) -> bool {
+        let mut received = self.received.lock().unwrap();
+        let copies = received
+            .entry((cmd.header.msg_ident, msg_type))
+            .or_insert_with(|| ReceivedCopies {
+                sender: cmd.header.process_identifier,
+                sector_idx: cmd.header.sector_idx,
+                count: 0,
+                first_received: Instant::now(),
+                answered: None,
+            });
+        copies.count += 1;
+        if copies.count <= self.discarded_copies {
+            return false;
+        }
+        copies.answered.get_or_insert_with(Instant::now);
+        true
+    }
+
+    async fn answer_and_send(&self, cmd: SystemRegisterCommand) {
+        let sender = cmd.header.process_identifier;
+        if let Some(reply) = self.answer(cmd).await {
+            self.send_to(sender, reply).await;
+        }
+    }
+
+    async fn answer(&self, cmd: SystemRegisterCommand) -> Option<SystemRegisterCommand> {
+        let mut sectors = self.sectors.lock().await;
+        let (timestamp, write_rank, data) = sectors
+            .entry(cmd.header.sector_idx)
+            .or_insert_with(|| (0, 0, SectorVec(vec![0; 4096])));
+
+        let content = match cmd.content {
+            SystemRegisterCommandContent::ReadProc => SystemRegisterCommandContent::Value {
+                timestamp: *timestamp,
+                write_rank: *write_rank,
+                sector_data: data.clone(),
+            },
+            SystemRegisterCommandContent::WriteProc {
+                timestamp: new_timestamp,
+                write_rank: new_write_rank,
+                data_to_write,
+            } => {
+                if (new_timestamp, new_write_rank) > (*timestamp, *write_rank) {
+                    *timestamp = new_timestamp;
+                    *write_rank = new_write_rank;
+                    *data = data_to_write;
+                }
+                SystemRegisterCommandContent::Ack
+            }
+            _ => return None,
+        };
+
+        Some(SystemRegisterCommand {
+            header: SystemCommandHeader {
+                process_identifier: self.self_rank,
+                msg_ident: cmd.header.msg_ident,
+                sector_idx: cmd.header.sector_idx,
+            },
+            content,
+        })
+    }
+
+    async fn send_to(&self, rank: u8, cmd: SystemRegisterCommand) {
+        let mut data = Vec::new();
+        serialize_register_command(
+            &RegisterCommand::System(cmd),
+            &mut data,
+            &self.hmac_system_key,
+        )
+        .await
+        .unwrap();
+
+        let mut outgoing = self.outgoing.lock().await;
+        // One retry, in case the process restarted and the old link broke
+        for _ in 0..2 {
+            let stream = match outgoing.entry(rank) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let location = self.tcp_locations.get(usize::from(rank - 1)).unwrap();
+                    match TcpStream::connect((location.0.as_str(), location.1)).await {
+                        Ok(stream) => entry.insert(stream),
+                        Err(_) => return,
+                    }
+                }
+            };
+            if stream.write_all(&data).await.is_ok() {
+                return;
+            }
+            outgoing.remove(&rank);
+        }
+    }
+}
@@ -0,0 +1,822 @@
+//! Network Block Device front-end of a register cluster, so that standard
+//! block device tooling (`nbd-client`, `nbdinfo`, `qemu-img`, ...) can use
+//! the drive. Every NBD request is translated into `ClientRegisterCommand`s
+//! for the sectors it covers, unaligned writes are done with a
+//! read-modify-write of the boundary sectors. Writes of the connections of
+//! one server lock the sectors they cover, so that the read-modify-write is
+//! atomic among them.
+//!
+//! Only the fixed newstyle handshake is supported, with options
+//! `NBD_OPT_EXPORT_NAME`, `NBD_OPT_GO`, `NBD_OPT_INFO`, `NBD_OPT_LIST` and
+//! `NBD_OPT_ABORT`, and simple replies to `NBD_CMD_READ`, `NBD_CMD_WRITE`,
+//! `NBD_CMD_FLUSH` and `NBD_CMD_DISC`. Requests of one NBD connection are
+//! executed one by one, sector commands of a request are pipelined.
+//!
+//! `NbdClient` is a minimal client of the same protocol subset, for testing
+//! without the kernel module.
+use crate::system::{
+    response_hmac_tag_is_ok, RegisterResponse, RegisterResponseContent, TestProcessesConfig,
+};
+use assignment_2_solution::{
+    serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec, StatusCode,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+pub const NBD_DEFAULT_PORT: u16 = 10809;
+pub const DEFAULT_EXPORT_NAME: &str = "atomic_disc_drive";
+pub const SECTOR_SIZE: u64 = 4096;
+/// Longest read or write the server accepts, as advertised in
+/// `NBD_INFO_BLOCK_SIZE`.
+pub const MAX_REQUEST_LENGTH: u32 = 32 << 20;
+/// Sector commands in flight on a connection to the cluster.
+const PIPELINE_DEPTH: usize = 32;
+/// Locks sectors of the export are spread over.
+const SECTOR_LOCK_STRIPES: usize = 256;
+/// Pause after a failed `accept`, as the error usually persists for a while.
+const ACCEPT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(10);
+
+const NBDMAGIC: u64 = 0x4e42_444d_4147_4943;
+const IHAVEOPT: u64 = 0x4948_4156_454f_5054;
+const REPLY_MAGIC: u64 = 0x0003_e889_0455_65a9;
+const REQUEST_MAGIC: u32 = 0x2560_9513;
+const SIMPLE_REPLY_MAGIC: u32 = 0x6744_6698;
+
+const FLAG_FIXED_NEWSTYLE: u16 = 1 << 0;
+const FLAG_NO_ZEROES: u16 = 1 << 1;
+
+const OPT_EXPORT_NAME: u32 = 1;
+const OPT_ABORT: u32 = 2;
+const OPT_LIST: u32 = 3;
+const OPT_INFO: u32 = 6;
+const OPT_GO: u32 = 7;
+
+const REP_ACK: u32 = 1;
+const REP_SERVER: u32 = 2;
+const REP_INFO: u32 = 3;
+const REP_ERR_UNSUP: u32 = (1 << 31) + 1;
+const REP_ERR_INVALID: u32 = (1 << 31) + 3;
+const REP_ERR_UNKNOWN: u32 = (1 << 31) + 6;
+
+const INFO_EXPORT: u16 = 0;
+const INFO_BLOCK_SIZE: u16 = 3;
+
+pub const TRANSMISSION_FLAG_HAS_FLAGS: u16 = 1 << 0;
+pub const TRANSMISSION_FLAG_SEND_FLUSH: u16 = 1 << 2;
+pub const TRANSMISSION_FLAG_SEND_FUA: u16 = 1 << 3;
+pub const TRANSMISSION_FLAG_CAN_MULTI_CONN: u16 = 1 << 8;
+/* Writes are durable once the register acknowledges them, so flushes and
+ * FUA are no-ops, and every connection sees the writes of the others.
+ * Multiple connections are safe only to a single server, which serializes
+ * their read-modify-writes. */
+const TRANSMISSION_FLAGS: u16 = TRANSMISSION_FLAG_HAS_FLAGS
+    | TRANSMISSION_FLAG_SEND_FLUSH
+    | TRANSMISSION_FLAG_SEND_FUA
+    | TRANSMISSION_FLAG_CAN_MULTI_CONN;
+
+const CMD_READ: u16 = 0;
+const CMD_WRITE: u16 = 1;
+const CMD_DISC: u16 = 2;
+const CMD_FLUSH: u16 = 3;
+
+/* Error values are fixed by the protocol, not taken from the platform */
+pub const NBD_EIO: u32 = 5;
+pub const NBD_EINVAL: u32 = 22;
+pub const NBD_ENOSPC: u32 = 28;
+
+pub struct NbdServer {
+    listener: TcpListener,
+    cluster: Arc<TestProcessesConfig>,
+    export_name: String,
+    rank: Option<usize>,
+}
+
+impl NbdServer {
+    /// Listens for NBD clients on `addr`. Each of them gets its own
+    /// connection to the cluster, to the process at index `rank` or, if it is
+    /// `None`, to the processes in turn.
+    pub async fn bind(
+        addr: impl ToSocketAddrs,
+        cluster: Arc<TestProcessesConfig>,
+        export_name: &str,
+        rank: Option<usize>,
+    ) -> std::io::Result<Self> {
+        Ok(NbdServer {
+            listener: TcpListener::bind(addr).await?,
+            cluster,
+            export_name: export_name.to_string(),
+            rank,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    pub fn export_size(&self) -> u64 {
+        self.cluster.n_sectors() * SECTOR_SIZE
+    }
+
+    pub async fn run(self) {
+        let connections = AtomicUsize::new(0);
+        let export = Arc::new(Export {
+            size: self.export_size(),
+            name: self.export_name,
+        });
+        let sector_locks = Arc::new(SectorLocks::new());
+        loop {
+            let (stream, peer) = match self.listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::warn!("NBD server could not accept a connection: {}", err);
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            let proc_idx = self.rank.unwrap_or_else(|| {
+                connections.fetch_add(1, Ordering::Relaxed) % self.cluster.tcp_locations.len()
+            });
+            let backend = ClusterBackend::new(self.cluster.clone(), proc_idx, sector_locks.clone());
+            let export = export.clone();
+            tokio::spawn(async move {
+                log::debug!("NBD client {} uses process {}", peer, proc_idx + 1);
+                if let Err(err) = serve_connection(stream, &export, backend).await {
+                    log::warn!("NBD client {} disconnected: {}", peer, err);
+                }
+            });
+        }
+    }
+}
+
+struct Export {
+    name: String,
+    size: u64,
+}
+
+impl Export {
+    fn matches(&self, name: &str) -> bool {
+        /* The empty name selects the default export */
+        name.is_empty() || name == self.name
+    }
+}
+
+async fn serve_connection(
+    mut stream: TcpStream,
+    export: &Export,
+    mut backend: ClusterBackend,
+) -> std::io::Result<()> {
+    if negotiate(&mut stream, export).await? {
+        transmit(&mut stream, export, &mut backend).await?;
+    }
+    Ok(())
+}
+
+/// Handshake and option haggling, returns whether the client entered the
+/// transmission phase.
+async fn negotiate(stream: &mut TcpStream, export: &Export) -> std::io::Result<bool> {
+    let mut greeting = Vec::new();
+    greeting.extend_from_slice(&NBDMAGIC.to_be_bytes());
+    greeting.extend_from_slice(&IHAVEOPT.to_be_bytes());
+    greeting.extend_from_slice(&(FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES).to_be_bytes());
+    stream.write_all(&greeting).await?;
+
+    let client_flags = stream.read_u32().await?;
+    if client_flags & FLAG_FIXED_NEWSTYLE as u32 == 0 {
+        return Err(protocol_error("client does not support fixed newstyle"));
+    }
+    let no_zeroes = client_flags & FLAG_NO_ZEROES as u32 != 0;
+
+    loop {
+        if stream.read_u64().await? != IHAVEOPT {
+            return Err(protocol_error("invalid option magic"));
+        }
+        let option = stream.read_u32().await?;
+        let length = stream.read_u32().await?;
+        if length > 4096 {
+            return Err(protocol_error("option data too long"));
+        }
+        let mut data = vec![0; length as usize];
+        stream.read_exact(&mut data).await?;
+
+        match option {
+            OPT_EXPORT_NAME => {
+                /* No way to report an error other than closing the connection */
+                if !export.matches(&String::from_utf8_lossy(&data)) {
+                    return Ok(false);
+                }
+                let mut reply = Vec::new();
+                reply.extend_from_slice(&export.size.to_be_bytes());
+                reply.extend_from_slice(&TRANSMISSION_FLAGS.to_be_bytes());
+                if !no_zeroes {
+                    reply.extend_from_slice(&[0; 124]);
+                }
+                stream.write_all(&reply).await?;
+                return Ok(true);
+            }
+            OPT_ABORT => {
+                send_option_reply(stream, option, REP_ACK, &[]).await?;
+                return Ok(false);
+            }
+            OPT_LIST => {
+                let mut server = Vec::new();
+                server.extend_from_slice(&(export.name.len() as u32).to_be_bytes());
+                server.extend_from_slice(export.name.as_bytes());
+                send_option_reply(stream, option, REP_SERVER, &server).await?;
+                send_option_reply(stream, option, REP_ACK, &[]).await?;
+            }
+            OPT_INFO | OPT_GO => {
+                let Some(name) = parse_info_request(&data) else {
+                    send_option_reply(stream, option, REP_ERR_INVALID, &[]).await?;
+                    continue;
+                };
+                if !export.matches(&name) {
+                    send_option_reply(stream, option, REP_ERR_UNKNOWN, &[]).await?;
+                    continue;
+                }
+                let mut info = Vec::new();
+                info.extend_from_slice(&INFO_EXPORT.to_be_bytes());
+                info.extend_from_slice(&export.size.to_be_bytes());
+                info.extend_from_slice(&TRANSMISSION_FLAGS.to_be_bytes());
+                send_option_reply(stream, option, REP_INFO, &info).await?;
+                /* Always sent, smaller requests are correct but slow */
+                let mut block_size = Vec::new();
+                block_size.extend_from_slice(&INFO_BLOCK_SIZE.to_be_bytes());
+                block_size.extend_from_slice(&1u32.to_be_bytes());
+                block_size.extend_from_slice(&(SECTOR_SIZE as u32).to_be_bytes());
+                block_size.extend_from_slice(&MAX_REQUEST_LENGTH.to_be_bytes());
+                send_option_reply(stream, option, REP_INFO, &block_size).await?;
+                send_option_reply(stream, option, REP_ACK, &[]).await?;
+                if option == OPT_GO {
+                    return Ok(true);
+                }
+            }
+            _ => send_option_reply(stream, option, REP_ERR_UNSUP, &[]).await?,
+        }
+    }
+}
+
+/// Export name of `NBD_OPT_INFO` and `NBD_OPT_GO`, the information requests
+/// following it are ignored.
+fn parse_info_request(data: &[u8]) -> Option<String> {
+    let name_length = u32::from_be_bytes(data.get(0..4)?.try_into().unwrap()) as usize;
+    let name = data.get(4..4 + name_length)?;
+    let requests = u16::from_be_bytes(
+        data.get(4 + name_length..6 + name_length)?
+            .try_into()
+            .unwrap(),
+    ) as usize;
+    if data.len() != 6 + name_length + 2 * requests {
+        return None;
+    }
+    Some(String::from_utf8_lossy(name).into_owned())
+}
+
+async fn send_option_reply(
+    stream: &mut TcpStream,
+    option: u32,
+    reply_type: u32,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut reply = Vec::new();
+    reply.extend_from_slice(&REPLY_MAGIC.to_be_bytes());
+    reply.extend_from_slice(&option.to_be_bytes());
+    reply.extend_from_slice(&reply_type.to_be_bytes());
+    reply.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    reply.extend_from_slice(data);
+    stream.write_all(&reply).await
+}
+
+async fn transmit(
+    stream: &mut TcpStream,
+    export: &Export,
+    backend: &mut ClusterBackend,
+) -> std::io::Result<()> {
+    loop {
+        let mut header = [0; 28];
+        match stream.read_exact(&mut header).await {
+            Ok(_) => {}
+            /* Disconnecting without NBD_CMD_DISC is rude, but common */
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        if u32::from_be_bytes(header[0..4].try_into().unwrap()) != REQUEST_MAGIC {
+            return Err(protocol_error("invalid request magic"));
+        }
+        let command = u16::from_be_bytes(header[6..8].try_into().unwrap());
+        let handle = u64::from_be_bytes(header[8..16].try_into().unwrap());
+        let offset = u64::from_be_bytes(header[16..24].try_into().unwrap());
+        let length = u32::from_be_bytes(header[24..28].try_into().unwrap());
+        let in_bounds = length <= MAX_REQUEST_LENGTH
+            && offset
+                .checked_add(length as u64)
+                .is_some_and(|end| end <= export.size);
+
+        match command {
+            CMD_READ => {
+                if !in_bounds {
+                    send_simple_reply(stream, NBD_EINVAL, handle, &[]).await?;
+                    continue;
+                }
+                match backend.read(offset, length).await {
+                    Ok(data) => send_simple_reply(stream, 0, handle, &data).await?,
+                    Err(err) => {
+                        log::warn!("NBD read of {}+{} failed: {}", offset, length, err);
+                        send_simple_reply(stream, NBD_EIO, handle, &[]).await?;
+                    }
+                }
+            }
+            CMD_WRITE => {
+                if length > MAX_REQUEST_LENGTH {
+                    return Err(protocol_error("write too long"));
+                }
+                let mut data = vec![0; length as usize];
+                stream.read_exact(&mut data).await?;
+                if !in_bounds {
+                    send_simple_reply(stream, NBD_ENOSPC, handle, &[]).await?;
+                    continue;
+                }
+                match backend.write(offset, &data).await {
+                    Ok(()) => send_simple_reply(stream, 0, handle, &[]).await?,
+                    Err(err) => {
+                        log::warn!("NBD write of {}+{} failed: {}", offset, length, err);
+                        send_simple_reply(stream, NBD_EIO, handle, &[]).await?;
+                    }
+                }
+            }
+            CMD_FLUSH => send_simple_reply(stream, 0, handle, &[]).await?,
+            CMD_DISC => return Ok(()),
+            _ => send_simple_reply(stream, NBD_EINVAL, handle, &[]).await?,
+        }
+    }
+}
+
+async fn send_simple_reply(
+    stream: &mut TcpStream,
+    error: u32,
+    handle: u64,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut reply = Vec::with_capacity(16 + data.len());
+    reply.extend_from_slice(&SIMPLE_REPLY_MAGIC.to_be_bytes());
+    reply.extend_from_slice(&error.to_be_bytes());
+    reply.extend_from_slice(&handle.to_be_bytes());
+    reply.extend_from_slice(data);
+    stream.write_all(&reply).await
+}
+
+fn protocol_error(what: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, what.to_string())
+}
+
+/// Locks of the sectors written by the connections of a server, a lock
+/// covers every `SECTOR_LOCK_STRIPES`-th sector. A read-modify-write holds the
+/// lock of its sector exclusively, so no other write of the sector lands
+/// between its read and its write. Whole sectors are written under a shared
+/// lock, which only keeps them from landing in such a gap.
+struct SectorLocks {
+    stripes: Vec<RwLock<()>>,
+}
+
+#[derive(Default)]
+struct SectorGuards<'a> {
+    _shared: Vec<RwLockReadGuard<'a, ()>>,
+    _exclusive: Vec<RwLockWriteGuard<'a, ()>>,
+}
+
+impl SectorLocks {
+    fn new() -> Self {
+        SectorLocks {
+            stripes: (0..SECTOR_LOCK_STRIPES).map(|_| RwLock::new(())).collect(),
+        }
+    }
+
+    /// Locks the sectors `first_sector..end_sector`, the ones in
+    /// `read_modified` exclusively.
+    async fn lock(
+        &self,
+        first_sector: u64,
+        end_sector: u64,
+        read_modified: &[u64],
+    ) -> SectorGuards<'_> {
+        let stripe = |sector_idx: u64| (sector_idx % SECTOR_LOCK_STRIPES as u64) as usize;
+        let mut exclusive: BTreeMap<usize, bool> = (first_sector..end_sector)
+            .take(SECTOR_LOCK_STRIPES)
+            .map(|sector_idx| (stripe(sector_idx), false))
+            .collect();
+        for sector_idx in read_modified {
+            exclusive.insert(stripe(*sector_idx), true);
+        }
+
+        /* Always in the same order, so that writes never deadlock */
+        let mut guards = SectorGuards::default();
+        for (stripe, exclusive) in exclusive {
+            if exclusive {
+                guards._exclusive.push(self.stripes[stripe].write().await);
+            } else {
+                guards._shared.push(self.stripes[stripe].read().await);
+            }
+        }
+        guards
+    }
+}
+
+/// Connection of an NBD client to one process of the cluster, opened again
+/// after it fails.
+struct ClusterBackend {
+    cluster: Arc<TestProcessesConfig>,
+    proc_idx: usize,
+    sector_locks: Arc<SectorLocks>,
+    stream: Option<TcpStream>,
+    next_request_identifier: u64,
+}
+
+impl ClusterBackend {
+    fn new(
+        cluster: Arc<TestProcessesConfig>,
+        proc_idx: usize,
+        sector_locks: Arc<SectorLocks>,
+    ) -> Self {
+        ClusterBackend {
+            cluster,
+            proc_idx,
+            sector_locks,
+            stream: None,
+            next_request_identifier: 0,
+        }
+    }
+
+    async fn read(&mut self, offset: u64, length: u32) -> Result<Vec<u8>, String> {
+        let first_sector = offset / SECTOR_SIZE;
+        let end_sector = (offset + length as u64).div_ceil(SECTOR_SIZE);
+        let sectors = self.read_sectors(first_sector, end_sector).await?;
+        let start = (offset - first_sector * SECTOR_SIZE) as usize;
+        Ok(sectors[start..start + length as usize].to_vec())
+    }
+
+    async fn write(&mut self, offset: u64, data: &[u8]) -> Result<(), String> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let end = offset + data.len() as u64;
+        let first_sector = offset / SECTOR_SIZE;
+        let end_sector = end.div_ceil(SECTOR_SIZE);
+        let last_sector = end_sector - 1;
+        let read_head = !offset.is_multiple_of(SECTOR_SIZE);
+        let read_tail =
+            !end.is_multiple_of(SECTOR_SIZE) && (last_sector != first_sector || !read_head);
+        let mut read_modified = Vec::new();
+        if read_head {
+            read_modified.push(first_sector);
+        }
+        if read_tail {
+            read_modified.push(last_sector);
+        }
+        let sector_locks = self.sector_locks.clone();
+        let _guards = sector_locks
+            .lock(first_sector, end_sector, &read_modified)
+            .await;
+
+        /* Boundary sectors are only partially overwritten */
+        let mut sectors = vec![0; ((end_sector - first_sector) * SECTOR_SIZE) as usize];
+        if read_head {
+            let head = self.read_sectors(first_sector, first_sector + 1).await?;
+            sectors[..SECTOR_SIZE as usize].copy_from_slice(&head);
+        }
+        if read_tail {
+            let tail = self.read_sectors(last_sector, end_sector).await?;
+            let tail_start = sectors.len() - SECTOR_SIZE as usize;
+            sectors[tail_start..].copy_from_slice(&tail);
+        }
+        let start = (offset - first_sector * SECTOR_SIZE) as usize;
+        sectors[start..start + data.len()].copy_from_slice(data);
+
+        let commands = sectors
+            .chunks(SECTOR_SIZE as usize)
+            .zip(first_sector..)
+            .map(|(sector, sector_idx)| {
+                (
+                    sector_idx,
+                    ClientRegisterCommandContent::Write {
+                        data: SectorVec(sector.to_vec()),
+                    },
+                )
+            })
+            .collect();
+        self.execute(commands).await.map(|_| ())
+    }
+
+    async fn read_sectors(
+        &mut self,
+        first_sector: u64,
+        end_sector: u64,
+    ) -> Result<Vec<u8>, String> {
+        let commands = (first_sector..end_sector)
+            .map(|sector_idx| (sector_idx, ClientRegisterCommandContent::Read))
+            .collect();
+        let mut data = Vec::with_capacity(((end_sector - first_sector) * SECTOR_SIZE) as usize);
+        for response in self.execute(commands).await? {
+            match response.content {
+                RegisterResponseContent::Read(SectorVec(sector)) => data.extend(sector),
+                RegisterResponseContent::Write => return Err("expected read response".to_string()),
+            }
+        }
+        Ok(data)
+    }
+
+    /// Executes the commands, at most `PIPELINE_DEPTH` at a time, and returns
+    /// their responses in the order of the commands.
+    async fn execute(
+        &mut self,
+        commands: Vec<(u64, ClientRegisterCommandContent)>,
+    ) -> Result<Vec<RegisterResponse>, String> {
+        let result = self.try_execute(commands).await;
+        if result.is_err() {
+            /* Late responses must not be mistaken for ones of later commands */
+            self.stream = None;
+        }
+        result
+    }
+
+    async fn try_execute(
+        &mut self,
+        commands: Vec<(u64, ClientRegisterCommandContent)>,
+    ) -> Result<Vec<RegisterResponse>, String> {
+        if self.stream.is_none() {
+            let (host, port) = &self.cluster.tcp_locations[self.proc_idx];
+            let stream = TcpStream::connect((host.as_str(), *port))
+                .await
+                .map_err(|err| {
+                    format!(
+                        "could not connect to process {}: {}",
+                        self.proc_idx + 1,
+                        err
+                    )
+                })?;
+            self.stream = Some(stream);
+        }
+        let stream = self.stream.as_mut().unwrap();
+
+        let first_identifier = self.next_request_identifier;
+        self.next_request_identifier += commands.len() as u64;
+        let mut responses: HashMap<u64, RegisterResponse> = HashMap::new();
+        let mut sent = 0;
+        while responses.len() < commands.len() {
+            if sent < commands.len() && sent - responses.len() < PIPELINE_DEPTH {
+                let (sector_idx, content) = commands[sent].clone();
+                let cmd = RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: first_identifier + sent as u64,
+                        sector_idx,
+                    },
+                    content,
+                });
+                let mut data = Vec::new();
+                serialize_register_command(&cmd, &mut data, &self.cluster.hmac_client_key)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                stream
+                    .write_all(&data)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                sent += 1;
+                continue;
+            }
+
+            let response = self.cluster.read_response(stream).await?;
+            let request_identifier = response.header.request_identifier;
+            if !(first_identifier..first_identifier + sent as u64).contains(&request_identifier) {
+                return Err(format!(
+                    "unexpected request identifier {}",
+                    request_identifier
+                ));
+            }
+            if !response_hmac_tag_is_ok(&response, &self.cluster.hmac_client_key) {
+                return Err("invalid HMAC tag".to_string());
+            }
+            if !matches!(response.header.status_code, StatusCode::Ok) {
+                let sector_idx = commands[(request_identifier - first_identifier) as usize].0;
+                return Err(format!(
+                    "status {} for sector {}",
+                    response.header.status_code as u8, sector_idx
+                ));
+            }
+            responses.insert(request_identifier, response);
+        }
+
+        Ok((0..commands.len() as u64)
+            .map(|idx| responses.remove(&(first_identifier + idx)).unwrap())
+            .collect())
+    }
+}
+
+/// Client of the protocol subset the server speaks. Errors reported by the
+/// server are returned as `std::io::Error`s with the NBD error value as the
+/// raw OS error, which matches `errno` on Linux.
+pub struct NbdClient {
+    stream: TcpStream,
+    size: u64,
+    transmission_flags: u16,
+    next_handle: u64,
+}
+
+impl NbdClient {
+    /// Connects with `NBD_OPT_GO`.
+    pub async fn connect(addr: impl ToSocketAddrs, export_name: &str) -> std::io::Result<Self> {
+        let mut stream = client_handshake(addr).await?;
+        let mut data = Vec::new();
+        data.extend_from_slice(&(export_name.len() as u32).to_be_bytes());
+        data.extend_from_slice(export_name.as_bytes());
+        data.extend_from_slice(&0u16.to_be_bytes());
+        send_option(&mut stream, OPT_GO, &data).await?;
+
+        let mut export = None;
+        loop {
+            let (reply_type, data) = read_option_reply(&mut stream, OPT_GO).await?;
+            match reply_type {
+                REP_ACK => break,
+                REP_INFO if data.get(0..2) == Some(&INFO_EXPORT.to_be_bytes()) => {
+                    if data.len() != 12 {
+                        return Err(protocol_error("invalid NBD_INFO_EXPORT"));
+                    }
+                    export = Some((
+                        u64::from_be_bytes(data[2..10].try_into().unwrap()),
+                        u16::from_be_bytes(data[10..12].try_into().unwrap()),
+                    ));
+                }
+                REP_INFO => {}
+                _ => {
+                    return Err(Error::other(format!(
+                        "server refused export {:?} with reply {:#x}",
+                        export_name, reply_type
+                    )))
+                }
+            }
+        }
+        let (size, transmission_flags) =
+            export.ok_or_else(|| protocol_error("no NBD_INFO_EXPORT"))?;
+        Ok(NbdClient {
+            stream,
+            size,
+            transmission_flags,
+            next_handle: 0,
+        })
+    }
+
+    /// Connects with the older `NBD_OPT_EXPORT_NAME`, which most kernel
+    /// clients still use.
+    pub async fn connect_with_export_name(
+        addr: impl ToSocketAddrs,
+        export_name: &str,
+    ) -> std::io::Result<Self> {
+        let mut stream = client_handshake(addr).await?;
+        send_option(&mut stream, OPT_EXPORT_NAME, export_name.as_bytes()).await?;
+        let size = stream.read_u64().await?;
+        let transmission_flags = stream.read_u16().await?;
+        Ok(NbdClient {
+            stream,
+            size,
+            transmission_flags,
+            next_handle: 0,
+        })
+    }
+
+    pub async fn list_exports(addr: impl ToSocketAddrs) -> std::io::Result<Vec<String>> {
+        let mut stream = client_handshake(addr).await?;
+        send_option(&mut stream, OPT_LIST, &[]).await?;
+        let mut exports = Vec::new();
+        loop {
+            let (reply_type, data) = read_option_reply(&mut stream, OPT_LIST).await?;
+            match reply_type {
+                REP_ACK => break,
+                REP_SERVER if data.len() >= 4 => {
+                    let name_length = u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize;
+                    let name = data
+                        .get(4..4 + name_length)
+                        .ok_or_else(|| protocol_error("invalid NBD_REP_SERVER"))?;
+                    exports.push(String::from_utf8_lossy(name).into_owned());
+                }
+                _ => return Err(protocol_error("unexpected reply to NBD_OPT_LIST")),
+            }
+        }
+        send_option(&mut stream, OPT_ABORT, &[]).await?;
+        read_option_reply(&mut stream, OPT_ABORT).await?;
+        Ok(exports)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn transmission_flags(&self) -> u16 {
+        self.transmission_flags
+    }
+
+    pub async fn read(&mut self, offset: u64, length: u32) -> std::io::Result<Vec<u8>> {
+        self.request(CMD_READ, offset, length, &[]).await?;
+        let mut data = vec![0; length as usize];
+        self.stream.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
+    pub async fn write(&mut self, offset: u64, data: &[u8]) -> std::io::Result<()> {
+        self.request(CMD_WRITE, offset, data.len() as u32, data)
+            .await
+    }
+
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        self.request(CMD_FLUSH, 0, 0, &[]).await
+    }
+
+    /// Sends `NBD_CMD_DISC`, which has no reply, and closes the connection.
+    pub async fn disconnect(mut self) -> std::io::Result<()> {
+        let header = self.request_header(CMD_DISC, 0, 0);
+        self.stream.write_all(&header).await?;
+        self.stream.shutdown().await
+    }
+
+    /// Sends a request and reads the simple reply, without the data of reads.
+    async fn request(
+        &mut self,
+        command: u16,
+        offset: u64,
+        length: u32,
+        data: &[u8],
+    ) -> std::io::Result<()> {
+        let mut request = self.request_header(command, offset, length).to_vec();
+        request.extend_from_slice(data);
+        self.stream.write_all(&request).await?;
+
+        if self.stream.read_u32().await? != SIMPLE_REPLY_MAGIC {
+            return Err(protocol_error("invalid reply magic"));
+        }
+        let error = self.stream.read_u32().await?;
+        let handle = self.stream.read_u64().await?;
+        if handle != self.next_handle - 1 {
+            return Err(protocol_error("reply to another request"));
+        }
+        match error {
+            0 => Ok(()),
+            _ => Err(Error::from_raw_os_error(error as i32)),
+        }
+    }
+
+    fn request_header(&mut self, command: u16, offset: u64, length: u32) -> [u8; 28] {
+        let mut header = [0; 28];
+        header[0..4].copy_from_slice(&REQUEST_MAGIC.to_be_bytes());
+        header[6..8].copy_from_slice(&command.to_be_bytes());
+        header[8..16].copy_from_slice(&self.next_handle.to_be_bytes());
+        header[16..24].copy_from_slice(&offset.to_be_bytes());
+        header[24..28].copy_from_slice(&length.to_be_bytes());
+        self.next_handle += 1;
+        header
+    }
+}
+
+async fn client_handshake(addr: impl ToSocketAddrs) -> std::io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+    if stream.read_u64().await? != NBDMAGIC || stream.read_u64().await? != IHAVEOPT {
+        return Err(protocol_error("not a newstyle NBD server"));
+    }
+    let server_flags = stream.read_u16().await?;
+    if server_flags & FLAG_FIXED_NEWSTYLE == 0 {
+        return Err(protocol_error("server does not support fixed newstyle"));
+    }
+    /* Without NO_ZEROES the reply to NBD_OPT_EXPORT_NAME is padded */
+    if server_flags & FLAG_NO_ZEROES == 0 {
+        return Err(protocol_error("server does not support NO_ZEROES"));
+    }
+    stream
+        .write_u32((FLAG_FIXED_NEWSTYLE | FLAG_NO_ZEROES) as u32)
+        .await?;
+    Ok(stream)
+}
+
+async fn send_option(stream: &mut TcpStream, option: u32, data: &[u8]) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    request.extend_from_slice(&IHAVEOPT.to_be_bytes());
+    request.extend_from_slice(&option.to_be_bytes());
+    request.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    request.extend_from_slice(data);
+    stream.write_all(&request).await
+}
+
+async fn read_option_reply(stream: &mut TcpStream, option: u32) -> std::io::Result<(u32, Vec<u8>)> {
+    if stream.read_u64().await? != REPLY_MAGIC || stream.read_u32().await? != option {
+        return Err(protocol_error("invalid option reply"));
+    }
+    let reply_type = stream.read_u32().await?;
+    let length = stream.read_u32().await?;
+    if length > 4096 {
+        return Err(protocol_error("option reply too long"));
+    }
+    let mut data = vec![0; length as usize];
+    stream.read_exact(&mut data).await?;
+    Ok((reply_type, data))
+}
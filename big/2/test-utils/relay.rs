@@ -0,0 +1,194 @@
+use crate::system::TestProcessesConfig;
+use assignment_2_solution::run_register_process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Duration;
+
+/// Pause after a failed `accept`, as the error usually persists for a while.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Pause after failing to listen again once allowed, for example while the
+/// port is still taken.
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Forwards TCP connections from its own port to a single process, and lets
+/// the test break those connections at will.
+pub struct Relay {
+    allowed: watch::Sender<bool>,
+    connections: Arc<Mutex<JoinSet<()>>>,
+    accepted: Arc<AtomicUsize>,
+    accept_task: JoinHandle<()>,
+}
+
+impl Relay {
+    /// Listens on `listen_on` before returning.
+    pub async fn start(
+        listen_on: (String, u16),
+        forward_to: (String, u16),
+    ) -> std::io::Result<Relay> {
+        let first_listener = TcpListener::bind((listen_on.0.as_str(), listen_on.1)).await?;
+        let (allowed, mut allowed_rx) = watch::channel(true);
+        let connections = Arc::new(Mutex::new(JoinSet::new()));
+        let accepted = Arc::new(AtomicUsize::new(0));
+
+        let accept_task = {
+            let connections = connections.clone();
+            let accepted = accepted.clone();
+            tokio::spawn(async move {
+                let mut first_listener = Some(first_listener);
+                loop {
+                    if allowed_rx.wait_for(|allowed| *allowed).await.is_err() {
+                        return;
+                    }
+                    // Not listening at all while refusing, so that connecting
+                    // fails instead of being accepted and closed
+                    let listener = match first_listener.take() {
+                        Some(listener) => listener,
+                        None => bind_retrying(&listen_on).await,
+                    };
+                    loop {
+                        let result = tokio::select! {
+                            result = listener.accept() => result,
+                            _ = allowed_rx.wait_for(|allowed| !*allowed) => break,
+                        };
+                        let incoming = match result {
+                            Ok((incoming, _)) => incoming,
+                            Err(err) => {
+                                log::warn!("Relay could not accept a connection: {}", err);
+                                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                                continue;
+                            }
+                        };
+                        accepted.fetch_add(1, Ordering::SeqCst);
+                        connections
+                            .lock()
+                            .unwrap()
+                            .spawn(forward(incoming, forward_to.clone()));
+                    }
+                }
+            })
+        };
+
+        Ok(Relay {
+            allowed,
+            connections,
+            accepted,
+            accept_task,
+        })
+    }
+
+    /// Closes all currently forwarded connections. New ones are still accepted.
+    pub fn sever(&self) {
+        // Dropping a JoinSet aborts its tasks, which drops both sockets
+        *self.connections.lock().unwrap() = JoinSet::new();
+    }
+
+    /// Stops listening, so that reconnect attempts are refused.
+    /// Existing connections are kept, call `sever` to close them too.
+    pub fn refuse(&self) {
+        self.allowed.send_replace(false);
+    }
+
+    pub fn allow(&self) {
+        self.allowed.send_replace(true);
+    }
+
+    /// Number of connections accepted since the relay was started.
+    pub fn accepted(&self) -> usize {
+        self.accepted.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        self.accept_task.abort();
+        self.sever();
+    }
+}
+
+/// Listens on `listen_on` again, waiting for a port which is still taken,
+/// rather than leaving the relay dead.
+async fn bind_retrying(listen_on: &(String, u16)) -> TcpListener {
+    loop {
+        match TcpListener::bind((listen_on.0.as_str(), listen_on.1)).await {
+            Ok(listener) => return listener,
+            Err(err) => {
+                log::warn!(
+                    "Relay could not listen on port {} again, retrying: {}",
+                    listen_on.1,
+                    err
+                );
+                tokio::time::sleep(BIND_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn forward(mut incoming: TcpStream, forward_to: (String, u16)) {
+    let Ok(mut outgoing) = TcpStream::connect((forward_to.0.as_str(), forward_to.1)).await else {
+        return;
+    };
+    let _ = tokio::io::copy_bidirectional(&mut incoming, &mut outgoing).await;
+}
+
+/// Processes of a `TestProcessesConfig` which reach each other only through
+/// relays. Every process listens on its own location from the config, but
+/// sees the other processes at the relay ports. Clients still connect to the
+/// processes directly.
+pub struct RelayedProcesses {
+    relays: Vec<Relay>,
+}
+
+impl RelayedProcesses {
+    pub async fn start(config: &TestProcessesConfig, relay_port_range_start: u16) -> Self {
+        let relay_locations: Vec<(String, u16)> = config
+            .tcp_locations
+            .iter()
+            .enumerate()
+            .map(|(idx, (host, _))| (host.clone(), relay_port_range_start + idx as u16))
+            .collect();
+        let mut relays = Vec::new();
+        for (relay, process) in relay_locations.iter().zip(config.tcp_locations.iter()) {
+            relays.push(
+                Relay::start(relay.clone(), process.clone())
+                    .await
+                    .expect("Could not bind relay"),
+            );
+        }
+
+        for proc_idx in 0..config.tcp_locations.len() {
+            let mut process_config = config.config(proc_idx);
+            process_config.public.tcp_locations = relay_locations.clone();
+            process_config.public.tcp_locations[proc_idx] = config.tcp_locations[proc_idx].clone();
+            tokio::spawn(run_register_process(process_config));
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        RelayedProcesses { relays }
+    }
+
+    /// Relay in front of the process `proc_idx`, that is the one carrying
+    /// messages sent to it by all other processes.
+    pub fn relay(&self, proc_idx: usize) -> &Relay {
+        self.relays.get(proc_idx).unwrap()
+    }
+
+    pub fn sever_all(&self) {
+        self.relays.iter().for_each(Relay::sever);
+    }
+
+    pub fn refuse_all(&self) {
+        self.relays.iter().for_each(Relay::refuse);
+    }
+
+    pub fn allow_all(&self) {
+        self.relays.iter().for_each(Relay::allow);
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.relays.iter().map(Relay::accepted).sum()
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, Instant};
+
+/// Resources held by the test binary, which runs the processes in its tasks.
+#[derive(Clone, Copy, Debug)]
+pub struct ResourceSample {
+    pub at: Duration,
+    pub rss_bytes: u64,
+    pub open_fds: usize,
+}
+
+impl ResourceSample {
+    pub fn take(start: Instant) -> Self {
+        ResourceSample {
+            at: start.elapsed(),
+            rss_bytes: rss_bytes(),
+            open_fds: open_fds(),
+        }
+    }
+}
+
+fn rss_bytes() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let kib: u64 = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .expect("No VmRSS in /proc/self/status")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .unwrap();
+    kib * 1024
+}
+
+fn open_fds() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+/// How much the resources may grow over the baseline taken before the
+/// processes were started.
+pub struct ResourceBudget {
+    pub rss_growth_bytes: u64,
+    pub fds_growth: usize,
+    /// Growth allowed once the workload has stopped, over the last sample
+    /// taken while it was running.
+    pub rss_growth_after_stop_bytes: u64,
+}
+
+impl Default for ResourceBudget {
+    fn default() -> Self {
+        ResourceBudget {
+            rss_growth_bytes: 64 * 1024 * 1024,
+            fds_growth: 256,
+            rss_growth_after_stop_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Samples resources in the background until dropped.
+pub struct ResourceMonitor {
+    start: Instant,
+    baseline: ResourceSample,
+    samples: Arc<Mutex<Vec<ResourceSample>>>,
+    task: JoinHandle<()>,
+}
+
+impl ResourceMonitor {
+    /// Call before starting the processes, the first sample is the baseline.
+    pub fn start(interval: Duration) -> Self {
+        let start = Instant::now();
+        let baseline = ResourceSample::take(start);
+        let samples = Arc::new(Mutex::new(vec![baseline]));
+        let task = {
+            let samples = samples.clone();
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    samples.lock().unwrap().push(ResourceSample::take(start));
+                }
+            })
+        };
+        ResourceMonitor {
+            start,
+            baseline,
+            samples,
+            task,
+        }
+    }
+
+    pub fn sample_now(&self) -> ResourceSample {
+        let sample = ResourceSample::take(self.start);
+        self.samples.lock().unwrap().push(sample);
+        sample
+    }
+
+    pub fn baseline(&self) -> ResourceSample {
+        self.baseline
+    }
+
+    pub fn samples(&self) -> Vec<ResourceSample> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    pub fn peak(&self) -> ResourceSample {
+        let samples = self.samples.lock().unwrap();
+        ResourceSample {
+            at: self.start.elapsed(),
+            rss_bytes: samples.iter().map(|s| s.rss_bytes).max().unwrap(),
+            open_fds: samples.iter().map(|s| s.open_fds).max().unwrap(),
+        }
+    }
+
+    /// Fails if any sample so far exceeded the budget.
+    pub fn assert_within(&self, budget: &ResourceBudget) {
+        for sample in self.samples() {
+            assert!(
+                sample.rss_bytes <= self.baseline.rss_bytes + budget.rss_growth_bytes,
+                "RSS grew from {} to {} bytes after {:?}",
+                self.baseline.rss_bytes,
+                sample.rss_bytes,
+                sample.at
+            );
+            assert!(
+                sample.open_fds <= self.baseline.open_fds + budget.fds_growth,
+                "Open file descriptors grew from {} to {} after {:?}",
+                self.baseline.open_fds,
+                sample.open_fds,
+                sample.at
+            );
+        }
+    }
+
+    /// Watches resources for `period` after the workload has stopped and
+    /// fails if they keep growing.
+    pub async fn assert_settles(&self, period: Duration, budget: &ResourceBudget) {
+        let at_stop = self.sample_now();
+        tokio::time::sleep(period).await;
+        let after = self.sample_now();
+        assert!(
+            after.rss_bytes <= at_stop.rss_bytes + budget.rss_growth_after_stop_bytes,
+            "RSS kept growing after the workload stopped, from {} to {} bytes",
+            at_stop.rss_bytes,
+            after.rss_bytes
+        );
+        assert!(
+            after.open_fds <= at_stop.open_fds,
+            "Open file descriptors kept growing after the workload stopped, from {} to {}",
+            at_stop.open_fds,
+            after.open_fds
+        );
+    }
+
+    pub fn report(&self) {
+        let peak = self.peak();
+        println!(
+            "baseline: {} KiB RSS, {} fds; peak: {} KiB RSS, {} fds",
+            self.baseline.rss_bytes / 1024,
+            self.baseline.open_fds,
+            peak.rss_bytes / 1024,
+            peak.open_fds
+        );
+    }
+}
+
+impl Drop for ResourceMonitor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
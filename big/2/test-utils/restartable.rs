@@ -0,0 +1,154 @@
+use crate::cluster_matrix::ClusterSource;
+use crate::external::write_process_config_file;
+use assignment_2_solution::{run_register_process, Configuration};
+use std::path::Path;
+use tempfile::TempDir;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::runtime::Runtime;
+use tokio::time::{Duration, Instant};
+
+/// Processes which can be killed and started again on the same storage
+/// directories, with another client key if needed. A process of the linked
+/// solution runs in its own runtime, so that it is killed together with all
+/// its tasks; an external one is a child process. The processes are stopped
+/// when it is dropped, so an owner of their storage directories declares it
+/// before them, to drop it first.
+pub struct RestartableProcesses {
+    source: ClusterSource,
+    process_configs: Vec<Configuration>,
+    processes: Vec<Option<RunningProcess>>,
+    config_dir: TempDir,
+}
+
+enum RunningProcess {
+    Linked(Runtime),
+    External(Child),
+}
+
+impl RestartableProcesses {
+    /// None of the processes runs until it is `restart`ed. Each of them
+    /// listens on its own entry of `tcp_locations` of its configuration.
+    pub fn new(source: ClusterSource, process_configs: Vec<Configuration>) -> Self {
+        RestartableProcesses {
+            source,
+            processes: process_configs.iter().map(|_| None).collect(),
+            process_configs,
+            config_dir: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.processes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+
+    pub fn is_running(&self, proc_idx: usize) -> bool {
+        self.processes[proc_idx].is_some()
+    }
+
+    /// The key the process verifies commands and signs responses with, once
+    /// it runs.
+    pub fn client_key(&self, proc_idx: usize) -> &[u8] {
+        &self.process_configs[proc_idx].hmac_client_key
+    }
+
+    /// The binary running the process, if it is an external one.
+    pub fn binary(&self, proc_idx: usize) -> Option<&Path> {
+        match &self.source {
+            ClusterSource::Linked => None,
+            ClusterSource::External(binaries) => Some(&binaries[proc_idx % binaries.len()]),
+        }
+    }
+
+    /// Drops the process with all its tasks and connections, keeping its
+    /// storage directory.
+    pub async fn kill(&mut self, proc_idx: usize) {
+        match self.processes[proc_idx].take() {
+            Some(RunningProcess::Linked(runtime)) => runtime.shutdown_background(),
+            Some(RunningProcess::External(mut child)) => child.kill().await.unwrap(),
+            None => {}
+        }
+    }
+
+    /// Starts the process again with `hmac_client_key` and waits until it
+    /// accepts connections. External processes read the key as a line of
+    /// text, so it must be ASCII for them.
+    pub async fn restart(&mut self, proc_idx: usize, hmac_client_key: &[u8]) {
+        if self.is_running(proc_idx) {
+            self.kill(proc_idx).await;
+            /* Let the old listener close */
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        self.process_configs[proc_idx].hmac_client_key = hmac_client_key.try_into().unwrap();
+        let process_config = self.process_configs[proc_idx].clone();
+        let process = match &self.source {
+            ClusterSource::Linked => {
+                let runtime = tokio::runtime::Builder::new_multi_thread()
+                    .worker_threads(2)
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                runtime.spawn(run_register_process(process_config));
+                RunningProcess::Linked(runtime)
+            }
+            ClusterSource::External(_) => {
+                let binary = self.binary(proc_idx).unwrap().to_path_buf();
+                RunningProcess::External(self.spawn_binary(&binary, process_config))
+            }
+        };
+        self.processes[proc_idx] = Some(process);
+        self.wait_for_listen(proc_idx).await;
+    }
+
+    fn spawn_binary(&self, binary: &Path, process_config: Configuration) -> Child {
+        let rank = process_config.public.self_rank;
+        let config_path = self.config_dir.path().join(format!("config-{}", rank));
+        write_process_config_file(&process_config, &config_path).unwrap();
+        Command::new(binary)
+            .arg(&config_path)
+            .arg(rank.to_string())
+            .arg(&process_config.public.storage_dir)
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap_or_else(|err| panic!("Could not run {}: {}", binary.display(), err))
+    }
+
+    async fn wait_for_listen(&mut self, proc_idx: usize) {
+        let (host, port) = self.process_configs[proc_idx].public.tcp_locations[proc_idx].clone();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while TcpStream::connect((host.as_str(), port)).await.is_err() {
+            if let Some(RunningProcess::External(child)) = self.processes[proc_idx].as_mut() {
+                if let Ok(Some(status)) = child.try_wait() {
+                    panic!(
+                        "{} of rank {} exited with {}",
+                        self.binary(proc_idx).unwrap().display(),
+                        proc_idx + 1,
+                        status
+                    );
+                }
+            }
+            assert!(
+                Instant::now() < deadline,
+                "Rank {} does not listen on port {}",
+                proc_idx + 1,
+                port
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for RestartableProcesses {
+    fn drop(&mut self) {
+        for process in &mut self.processes {
+            /* Children are killed on drop */
+            if let Some(RunningProcess::Linked(runtime)) = process.take() {
+                runtime.shutdown_background();
+            }
+        }
+    }
+}
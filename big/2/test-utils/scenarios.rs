@@ -0,0 +1,243 @@
+//! System scenarios shared by the tests of the linked solution and the
+//! interop tests, which run them on clusters mixing `atomic_disc_drive`
+//! binaries. Each runs on one `ClusterShape`, started from a `ClusterSource`.
+use crate::cluster_matrix::{ClusterShape, ClusterSource};
+use crate::system::RegisterResponseContent;
+use assignment_2_solution::{
+    deserialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec, SystemRegisterCommandContent,
+};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use tokio::net::TcpListener;
+
+pub async fn concurrent_operations_on_the_same_sector(source: &ClusterSource, shape: ClusterShape) {
+    // given
+    let n_clients = 16;
+    let cluster = source.start(&shape).await;
+    let config = cluster.config();
+    let mut streams = Vec::new();
+    for _ in 0..n_clients {
+        streams.push(config.connect(shape.client_proc_idx).await);
+    }
+    // when
+    for (i, stream) in streams.iter_mut().enumerate() {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: i.try_into().unwrap(),
+                        sector_idx: 0,
+                    },
+                    content: ClientRegisterCommandContent::Write {
+                        data: SectorVec(vec![if i % 2 == 0 { 1 } else { 254 }; 4096]),
+                    },
+                }),
+                stream,
+            )
+            .await;
+    }
+
+    for stream in &mut streams {
+        config.read_response(stream).await.unwrap();
+    }
+
+    config
+        .send_cmd(
+            &RegisterCommand::Client(ClientRegisterCommand {
+                header: ClientCommandHeader {
+                    request_identifier: n_clients,
+                    sector_idx: 0,
+                },
+                content: ClientRegisterCommandContent::Read,
+            }),
+            &mut streams[0],
+        )
+        .await;
+    let response = config.read_response(&mut streams[0]).await.unwrap();
+
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => {
+            assert!(sector == vec![1; 4096] || sector == vec![254; 4096]);
+        }
+        _ => panic!("Expected read response"),
+    }
+}
+
+pub async fn large_number_of_operations_execute_successfully(
+    source: &ClusterSource,
+    shape: ClusterShape,
+) {
+    // given
+    let commands_total = 32;
+    let cluster = source.start(&shape).await;
+    let config = cluster.config();
+    let mut stream = config.connect(shape.client_proc_idx).await;
+
+    for cmd_idx in 0..commands_total {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: cmd_idx,
+                        sector_idx: cmd_idx,
+                    },
+                    content: ClientRegisterCommandContent::Write {
+                        data: SectorVec(vec![cmd_idx as u8; 4096]),
+                    },
+                }),
+                &mut stream,
+            )
+            .await;
+    }
+
+    for _ in 0..commands_total {
+        config.read_response(&mut stream).await.unwrap();
+    }
+
+    // when
+    for cmd_idx in 0..commands_total {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: cmd_idx + 256,
+                        sector_idx: cmd_idx,
+                    },
+                    content: ClientRegisterCommandContent::Read,
+                }),
+                &mut stream,
+            )
+            .await;
+    }
+
+    // then
+    for _ in 0..commands_total {
+        let response = config.read_response(&mut stream).await.unwrap();
+        match response.content {
+            RegisterResponseContent::Read(SectorVec(sector)) => {
+                assert_eq!(
+                    sector,
+                    vec![(response.header.request_identifier - 256) as u8; 4096]
+                )
+            }
+            _ => panic!("Expected read response"),
+        }
+    }
+}
+
+/// Needs at least three processes, as the stub never answers and the others
+/// must form a majority without it.
+pub async fn concurrent_writes_are_serialized(source: &ClusterSource, shape: ClusterShape) {
+    // given
+    let n_clients = 16;
+    /* Spawn all but one and add our stub to system */
+    let stub_idx = if shape.client_proc_idx == shape.processes_count - 1 {
+        0
+    } else {
+        shape.processes_count - 1
+    };
+    let listener = TcpListener::bind(shape.config().tcp_locations[stub_idx].clone())
+        .await
+        .unwrap();
+    let cluster = source
+        .start_some(
+            &shape,
+            (0..shape.processes_count).filter(|idx| *idx != stub_idx),
+        )
+        .await;
+    let config = cluster.config();
+
+    let mut streams = Vec::new();
+    for _ in 0..n_clients {
+        streams.push(config.connect(shape.client_proc_idx).await);
+    }
+    // when
+    for (i, stream) in streams.iter_mut().enumerate() {
+        config
+            .send_cmd(
+                &RegisterCommand::Client(ClientRegisterCommand {
+                    header: ClientCommandHeader {
+                        request_identifier: i.try_into().unwrap(),
+                        sector_idx: 0,
+                    },
+                    content: ClientRegisterCommandContent::Write {
+                        data: SectorVec(vec![if i % 2 == 0 { 1 } else { 254 }; 4096]),
+                    },
+                }),
+                stream,
+            )
+            .await;
+    }
+
+    let mut receivers = Vec::new();
+    for _ in 0..shape.processes_count - 1 {
+        let (receiver, _addr) = listener.accept().await.unwrap();
+        receivers.push(receiver);
+    }
+
+    for stream in &mut streams {
+        config.read_response(stream).await.unwrap();
+    }
+
+    config
+        .send_cmd(
+            &RegisterCommand::Client(ClientRegisterCommand {
+                header: ClientCommandHeader {
+                    request_identifier: n_clients,
+                    sector_idx: 0,
+                },
+                content: ClientRegisterCommandContent::Read,
+            }),
+            &mut streams[0],
+        )
+        .await;
+    let response = config.read_response(&mut streams[0]).await.unwrap();
+
+    match response.content {
+        RegisterResponseContent::Read(SectorVec(sector)) => {
+            assert!(sector == vec![1; 4096] || sector == vec![254; 4096]);
+        }
+        RegisterResponseContent::Write => panic!("Expected read response"),
+    }
+
+    // then
+    let mut receiving_set = tokio::task::JoinSet::new();
+    for _ in 0..shape.processes_count - 1 {
+        let mut receiver = receivers.pop().unwrap();
+        let hmac_system_key: [u8; 64] = config.hmac_system_key.clone().try_into().unwrap();
+        let hmac_client_key: [u8; 32] = config.hmac_client_key.clone().try_into().unwrap();
+
+        receiving_set.spawn(async move {
+            let mut data_written: HashMap<u64, SectorVec> = HashMap::new();
+            loop {
+                let (message, _) =
+                    deserialize_register_command(&mut receiver, &hmac_system_key, &hmac_client_key)
+                        .await
+                        .unwrap();
+                let RegisterCommand::System(cmd) = message else {
+                    continue;
+                };
+                let SystemRegisterCommandContent::WriteProc {
+                    timestamp,
+                    write_rank: _,
+                    data_to_write,
+                } = cmd.content
+                else {
+                    continue;
+                };
+
+                if let Some(val) = data_written.get(&timestamp) {
+                    assert_eq!(val.0, data_to_write.0);
+                }
+
+                data_written.insert(timestamp, data_to_write);
+                if timestamp >= n_clients {
+                    break;
+                }
+            }
+        });
+    }
+    // some of these tasks will get stuck in deserialize_register_command, it's ok
+    receiving_set.join_next().await;
+}
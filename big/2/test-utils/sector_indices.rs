@@ -0,0 +1,62 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Valid indices at both ends of a drive of `n_sectors` sectors.
+pub fn boundary_sector_indices(n_sectors: u64) -> Vec<u64> {
+    let mut indices = vec![
+        0,
+        1,
+        n_sectors / 2,
+        n_sectors.saturating_sub(2),
+        n_sectors.saturating_sub(1),
+    ];
+    indices.retain(|idx| *idx < n_sectors);
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Indices just past the end of a drive of `n_sectors` sectors, and ones
+/// which overflow when multiplied by the sector size or cast to signed.
+pub fn invalid_sector_indices(n_sectors: u64) -> Vec<u64> {
+    let mut indices = vec![
+        n_sectors,
+        n_sectors + 1,
+        u64::from(u32::MAX),
+        u64::MAX / 4096 + 1,
+        1 << 63,
+        u64::MAX - 1,
+        u64::MAX,
+    ];
+    indices.retain(|idx| *idx >= n_sectors);
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+}
+
+/// Distinct indices spread over the whole `0..n_sectors` range, so that
+/// most of them are far apart. The same `seed` gives the same indices.
+pub fn sparse_sector_indices(seed: u64, count: usize, n_sectors: u64) -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut indices = Vec::with_capacity(count);
+    while indices.len() < count {
+        let idx = rng.gen_range(0..n_sectors);
+        if !indices.contains(&idx) {
+            indices.push(idx);
+        }
+    }
+    indices
+}
+
+/// Indices a sectors manager must store, as it does not know `n_sectors`.
+pub fn extreme_sector_indices() -> Vec<u64> {
+    vec![
+        u64::from(u32::MAX),
+        u64::from(u32::MAX) + 1,
+        u64::MAX / 4096,
+        u64::MAX / 4096 + 1,
+        1 << 63,
+        u64::MAX - 1,
+        u64::MAX,
+    ]
+}
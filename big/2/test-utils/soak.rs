@@ -0,0 +1,546 @@
+use crate::cluster_matrix::ClusterSource;
+use crate::linearizability::{
+    CheckError, LinearizabilityChecker, ValueId, Violation, INITIAL_VALUE,
+};
+use crate::relay::Relay;
+use crate::restartable::RestartableProcesses;
+use crate::system::{RegisterResponseContent, TestProcessesConfig};
+use assignment_2_solution::{
+    serialize_register_command, ClientCommandHeader, ClientRegisterCommand,
+    ClientRegisterCommandContent, RegisterCommand, SectorVec, StatusCode,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::{Duration, Instant};
+
+pub struct SoakConfig {
+    pub seed: u64,
+    pub duration: Duration,
+    pub processes: usize,
+    pub clients: usize,
+    pub sectors: u64,
+    /// Processes listen on consecutive ports starting here, their relays
+    /// on the ones right after.
+    pub port_range_start: u16,
+    /// Mean time between two faults.
+    pub fault_interval: Duration,
+    /// Operations without a response for this long are abandoned.
+    pub op_timeout: Duration,
+    pub trace_path: PathBuf,
+}
+
+impl SoakConfig {
+    pub fn new(seed: u64, duration: Duration) -> Self {
+        SoakConfig {
+            seed,
+            duration,
+            processes: 3,
+            clients: 6,
+            sectors: 16,
+            port_range_start: 23000,
+            fault_interval: Duration::from_secs(5),
+            op_timeout: Duration::from_secs(30),
+            trace_path: PathBuf::from(format!("soak-trace-{}.txt", seed)),
+        }
+    }
+}
+
+pub struct SoakReport {
+    pub ops_checked: u64,
+    pub segments_checked: u64,
+    pub abandoned: u64,
+    pub faults: u64,
+}
+
+pub enum SoakFailure {
+    NotLinearizable(Violation),
+    /// The checker gave up, the run neither passed nor failed
+    Inconclusive(String),
+    /// A response which is wrong on its own, regardless of other operations
+    Protocol(String),
+}
+
+impl std::fmt::Display for SoakFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SoakFailure::NotLinearizable(violation) => write!(f, "{}", violation),
+            SoakFailure::Inconclusive(description) => writeln!(f, "{}", description),
+            SoakFailure::Protocol(description) => writeln!(f, "{}", description),
+        }
+    }
+}
+
+/// Restartable processes of a `TestProcessesConfig`. The processes reach each
+/// other through relays, clients connect to them directly.
+pub struct SoakCluster {
+    config: Arc<TestProcessesConfig>,
+    processes: RestartableProcesses,
+    relays: Vec<Relay>,
+    disk_dir: tempfile::TempDir,
+}
+
+impl SoakCluster {
+    pub async fn start(processes: usize, port_range_start: u16) -> Self {
+        let config = TestProcessesConfig::new(processes, port_range_start);
+        let relay_locations: Vec<(String, u16)> = (0..processes)
+            .map(|idx| {
+                (
+                    "localhost".to_string(),
+                    port_range_start + (processes + idx) as u16,
+                )
+            })
+            .collect();
+        let mut relays = Vec::new();
+        for (relay, process) in relay_locations.iter().zip(config.tcp_locations.iter()) {
+            relays.push(
+                Relay::start(relay.clone(), process.clone())
+                    .await
+                    .expect("Could not bind relay"),
+            );
+        }
+        let process_configs = (0..processes)
+            .map(|proc_idx| {
+                let mut process_config = config.config(proc_idx);
+                process_config.public.tcp_locations = relay_locations.clone();
+                process_config.public.tcp_locations[proc_idx] =
+                    config.tcp_locations[proc_idx].clone();
+                process_config
+            })
+            .collect();
+
+        let mut cluster = SoakCluster {
+            processes: RestartableProcesses::new(ClusterSource::Linked, process_configs),
+            config: Arc::new(config),
+            relays,
+            disk_dir: tempfile::tempdir().unwrap(),
+        };
+        for proc_idx in 0..processes {
+            cluster.restart(proc_idx).await;
+        }
+        cluster
+    }
+
+    pub fn config(&self) -> Arc<TestProcessesConfig> {
+        self.config.clone()
+    }
+
+    pub fn relay(&self, proc_idx: usize) -> &Relay {
+        &self.relays[proc_idx]
+    }
+
+    pub fn is_running(&self, proc_idx: usize) -> bool {
+        self.processes.is_running(proc_idx)
+    }
+
+    /// Drops the process with all its tasks and connections, keeping its
+    /// storage directory.
+    pub async fn kill(&mut self, proc_idx: usize) {
+        self.processes.kill(proc_idx).await;
+    }
+
+    /// Starts the process again and waits until it accepts connections.
+    pub async fn restart(&mut self, proc_idx: usize) {
+        self.processes
+            .restart(proc_idx, &self.config.hmac_client_key)
+            .await;
+    }
+
+    /// Keeps the filesystem of the temporary directory busy with large synced
+    /// writes. The processes build their sectors managers on their own, so
+    /// this only adds contention, and slows them only as far as their storage
+    /// directories share that filesystem and device.
+    pub async fn load_disk(&self, period: Duration) {
+        let path = self.disk_dir.path().join("load");
+        tokio::task::spawn_blocking(move || {
+            let deadline = std::time::Instant::now() + period;
+            let chunk = vec![0x5a_u8; 16 * 1024 * 1024];
+            while std::time::Instant::now() < deadline {
+                let mut file = std::fs::File::create(&path).unwrap();
+                file.write_all(&chunk).unwrap();
+                file.sync_all().unwrap();
+            }
+            let _ = std::fs::remove_file(&path);
+        })
+        .await
+        .unwrap();
+    }
+}
+
+/// Last events of the run, written to the trace file on failure.
+#[derive(Clone)]
+pub struct Trace {
+    start: Instant,
+    events: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Trace {
+    const CAPACITY: usize = 100_000;
+
+    pub fn new() -> Self {
+        Trace {
+            start: Instant::now(),
+            events: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    pub fn record(&self, event: String) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() == Self::CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(format!("{:>12.3?} {}", self.start.elapsed(), event));
+    }
+
+    pub fn write(&self, path: &std::path::Path, header: &str) -> std::io::Result<()> {
+        let mut file = std::fs::File::create(path)?;
+        writeln!(file, "{}", header)?;
+        for event in self.events.lock().unwrap().iter() {
+            writeln!(file, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type OpKey = (usize, u64);
+
+enum HistoryEvent {
+    Invoke {
+        key: OpKey,
+        sector_idx: u64,
+        write: Option<ValueId>,
+    },
+    Complete {
+        key: OpKey,
+        read: Option<ValueId>,
+    },
+    Abandon {
+        key: OpKey,
+    },
+    Failure(SoakFailure),
+}
+
+/// Sector contents carrying the identifier of the write which stored them.
+pub fn sector_of_value(value: ValueId) -> SectorVec {
+    let mut data = vec![value as u8; 4096];
+    data[..8].copy_from_slice(&value.to_be_bytes());
+    SectorVec(data)
+}
+
+/// Inverse of `sector_of_value`. Contents no write could have stored map
+/// to `u64::MAX`, which is never written and so never linearizable.
+pub fn value_of_sector(sector: &SectorVec) -> ValueId {
+    if sector.0.iter().all(|byte| *byte == 0) {
+        return INITIAL_VALUE;
+    }
+    let value = u64::from_be_bytes(sector.0[..8].try_into().unwrap());
+    if sector.0[8..].iter().all(|byte| *byte == value as u8) {
+        value
+    } else {
+        u64::MAX
+    }
+}
+
+/// Runs the soak test until `config.duration` passes or the first failure,
+/// which is written to `config.trace_path` along with the seed. The seed
+/// fixes the random choices of the run, not the timing of the cluster, so
+/// running with it again does not necessarily reproduce the failure.
+pub async fn run_soak(config: SoakConfig) -> Result<SoakReport, SoakFailure> {
+    let trace = Trace::new();
+    trace.record(format!(
+        "seed {}, {} processes, {} clients, {} sectors",
+        config.seed, config.processes, config.clients, config.sectors
+    ));
+    let mut cluster = SoakCluster::start(config.processes, config.port_range_start).await;
+    let (events_tx, events_rx) = unbounded_channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let abandoned = Arc::new(AtomicU64::new(0));
+
+    let checker = tokio::spawn(check_history(events_rx, trace.clone()));
+    let clients: Vec<_> = (0..config.clients)
+        .map(|client| {
+            let client_config = ClientConfig {
+                client,
+                seed: config.seed,
+                sectors: config.sectors,
+                op_timeout: config.op_timeout,
+                processes: cluster.config(),
+            };
+            tokio::spawn(run_client(
+                client_config,
+                events_tx.clone(),
+                stop.clone(),
+                abandoned.clone(),
+                trace.clone(),
+            ))
+        })
+        .collect();
+    drop(events_tx);
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let deadline = Instant::now() + config.duration;
+    let mut faults = 0;
+    tokio::pin!(checker);
+    let checker_result = loop {
+        let pause = config.fault_interval.mul_f64(rng.gen_range(0.5..1.5));
+        tokio::select! {
+            result = &mut checker => break Some(result.unwrap()),
+            _ = tokio::time::sleep_until(deadline) => break None,
+            _ = tokio::time::sleep(pause) => {
+                faults += 1;
+                inject_fault(&mut cluster, &mut rng, &trace).await;
+            }
+        }
+    };
+
+    stop.store(true, Ordering::SeqCst);
+    for client in clients {
+        client.abort();
+    }
+    let result = match checker_result {
+        Some(result) => result,
+        None => checker.await.unwrap(),
+    };
+    match result {
+        Ok(mut report) => {
+            report.abandoned = abandoned.load(Ordering::SeqCst);
+            report.faults = faults;
+            Ok(report)
+        }
+        Err(failure) => {
+            let header = format!(
+                "seed: {}\nprocesses: {}, clients: {}, sectors: {}\n\n{}",
+                config.seed, config.processes, config.clients, config.sectors, failure
+            );
+            if let Err(err) = trace.write(&config.trace_path, &header) {
+                log::error!("Could not write {}: {}", config.trace_path.display(), err);
+            }
+            Err(failure)
+        }
+    }
+}
+
+async fn inject_fault(cluster: &mut SoakCluster, rng: &mut StdRng, trace: &Trace) {
+    let proc_idx = rng.gen_range(0..cluster.processes.len());
+    let period = Duration::from_millis(rng.gen_range(500..5000));
+    match rng.gen_range(0..4) {
+        0 => {
+            trace.record(format!(
+                "fault: restarting process {} after {:?}",
+                proc_idx, period
+            ));
+            cluster.kill(proc_idx).await;
+            tokio::time::sleep(period).await;
+            cluster.restart(proc_idx).await;
+        }
+        1 => {
+            trace.record(format!("fault: severing links to process {}", proc_idx));
+            cluster.relay(proc_idx).sever();
+        }
+        2 => {
+            /* Its relay carries only the links into it, the process still
+             * reaches the others */
+            trace.record(format!(
+                "fault: isolating inbound links of process {} for {:?}",
+                proc_idx, period
+            ));
+            cluster.relay(proc_idx).refuse();
+            cluster.relay(proc_idx).sever();
+            tokio::time::sleep(period).await;
+            cluster.relay(proc_idx).allow();
+        }
+        _ => {
+            trace.record(format!(
+                "fault: loading the filesystem of the temp dir for {:?}",
+                period
+            ));
+            cluster.load_disk(period).await;
+        }
+    }
+    trace.record("fault: over".to_string());
+}
+
+async fn check_history(
+    mut events: UnboundedReceiver<HistoryEvent>,
+    trace: Trace,
+) -> Result<SoakReport, SoakFailure> {
+    /* Outcomes of writes abandoned because of a restart are surely known
+     * by the time a few more restarts happened */
+    let mut checker = LinearizabilityChecker::new(Duration::from_secs(120));
+    let mut ops = HashMap::new();
+    while let Some(event) = events.recv().await {
+        let result = match event {
+            HistoryEvent::Invoke {
+                key,
+                sector_idx,
+                write,
+            } => {
+                let id = checker.invoke(key.0, sector_idx, write);
+                ops.insert(key, (sector_idx, id));
+                Ok(())
+            }
+            HistoryEvent::Complete { key, read } => {
+                let (sector_idx, id) = ops.remove(&key).unwrap();
+                checker.complete(sector_idx, id, read)
+            }
+            HistoryEvent::Abandon { key } => {
+                let (sector_idx, id) = ops.remove(&key).unwrap();
+                checker.abandon(sector_idx, id)
+            }
+            HistoryEvent::Failure(failure) => return Err(failure),
+        };
+        match result {
+            Ok(()) => {}
+            Err(CheckError::NotLinearizable(violation)) => {
+                trace.record(format!("violation on sector {}", violation.sector_idx));
+                return Err(SoakFailure::NotLinearizable(violation));
+            }
+            Err(err @ CheckError::Inconclusive { .. }) => {
+                trace.record(format!("check of sector {} inconclusive", err.sector_idx()));
+                return Err(SoakFailure::Inconclusive(err.to_string()));
+            }
+        }
+    }
+    Ok(SoakReport {
+        ops_checked: checker.ops_checked,
+        segments_checked: checker.segments_checked,
+        abandoned: 0,
+        faults: 0,
+    })
+}
+
+struct ClientConfig {
+    client: usize,
+    seed: u64,
+    sectors: u64,
+    op_timeout: Duration,
+    processes: Arc<TestProcessesConfig>,
+}
+
+async fn run_client(
+    config: ClientConfig,
+    events: UnboundedSender<HistoryEvent>,
+    stop: Arc<AtomicBool>,
+    abandoned: Arc<AtomicU64>,
+    trace: Trace,
+) {
+    let mut rng = StdRng::seed_from_u64(config.seed ^ ((config.client as u64 + 1) << 32));
+    let processes = &config.processes;
+    let mut streams: HashMap<usize, TcpStream> = HashMap::new();
+    let mut seq = 0;
+
+    while !stop.load(Ordering::SeqCst) {
+        seq += 1;
+        let proc_idx = rng.gen_range(0..processes.tcp_locations.len());
+        let sector_idx = rng.gen_range(0..config.sectors);
+        /* Unique among all clients, and never INITIAL_VALUE */
+        let write = rng
+            .gen_bool(0.5)
+            .then_some(((config.client as u64 + 1) << 40) | seq);
+        let key = (config.client, seq);
+
+        if let std::collections::hash_map::Entry::Vacant(entry) = streams.entry(proc_idx) {
+            let location = &processes.tcp_locations[proc_idx];
+            match TcpStream::connect((location.0.as_str(), location.1)).await {
+                Ok(stream) => {
+                    entry.insert(stream);
+                }
+                Err(_) => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            }
+        }
+        let stream = streams.get_mut(&proc_idx).unwrap();
+
+        let cmd = RegisterCommand::Client(ClientRegisterCommand {
+            header: ClientCommandHeader {
+                request_identifier: seq,
+                sector_idx,
+            },
+            content: match write {
+                Some(value) => ClientRegisterCommandContent::Write {
+                    data: sector_of_value(value),
+                },
+                None => ClientRegisterCommandContent::Read,
+            },
+        });
+        let mut data = Vec::new();
+        serialize_register_command(&cmd, &mut data, &processes.hmac_client_key)
+            .await
+            .unwrap();
+
+        let _ = events.send(HistoryEvent::Invoke {
+            key,
+            sector_idx,
+            write,
+        });
+        let response = async {
+            stream
+                .write_all(&data)
+                .await
+                .map_err(|err| err.to_string())?;
+            processes.read_response(stream).await
+        };
+        let response = match tokio::time::timeout(config.op_timeout, response).await {
+            Ok(Ok(response)) => response,
+            outcome => {
+                let reason = match outcome {
+                    Ok(Err(err)) => err,
+                    _ => "timeout".to_string(),
+                };
+                trace.record(format!(
+                    "client {} abandons op {} on process {}: {}",
+                    config.client, seq, proc_idx, reason
+                ));
+                abandoned.fetch_add(1, Ordering::SeqCst);
+                streams.remove(&proc_idx);
+                let _ = events.send(HistoryEvent::Abandon { key });
+                continue;
+            }
+        };
+
+        let problem = if response.header.request_identifier != seq {
+            Some(format!(
+                "expected response to request {}, got {}",
+                seq, response.header.request_identifier
+            ))
+        } else if !matches!(response.header.status_code, StatusCode::Ok) {
+            Some(format!("status {}", response.header.status_code as u8))
+        } else if !crate::system::response_hmac_tag_is_ok(&response, &processes.hmac_client_key) {
+            Some("invalid HMAC tag".to_string())
+        } else {
+            match (&response.content, write) {
+                (RegisterResponseContent::Write, Some(_)) => None,
+                (RegisterResponseContent::Read(_), None) => None,
+                _ => Some("response of a wrong type".to_string()),
+            }
+        };
+        if let Some(problem) = problem {
+            let _ = events.send(HistoryEvent::Failure(SoakFailure::Protocol(format!(
+                "Process {} answered client {} about sector {}: {}",
+                proc_idx, config.client, sector_idx, problem
+            ))));
+            return;
+        }
+
+        let read = match response.content {
+            RegisterResponseContent::Read(sector) => Some(value_of_sector(&sector)),
+            RegisterResponseContent::Write => None,
+        };
+        let _ = events.send(HistoryEvent::Complete { key, read });
+    }
+}
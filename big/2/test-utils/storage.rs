@@ -0,0 +1,278 @@
+use assignment_2_solution::{build_sectors_manager, SectorVec, SectorsManager};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Files found in a storage directory, with their sizes.
+pub struct StorageFootprint {
+    pub files: Vec<(PathBuf, u64)>,
+}
+
+impl StorageFootprint {
+    pub fn of(dir: &Path) -> Self {
+        let mut files = Vec::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let entry = entry.unwrap();
+                let metadata = entry.metadata().unwrap();
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                } else {
+                    files.push((entry.path(), metadata.len()));
+                }
+            }
+        }
+        StorageFootprint { files }
+    }
+
+    pub fn files_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|(_, len)| len).sum()
+    }
+
+    /// Paths of the files relative to `dir`, the directory this footprint
+    /// was taken of.
+    pub fn relative_paths(&self, dir: &Path) -> HashSet<PathBuf> {
+        self.files
+            .iter()
+            .map(|(path, _)| path.strip_prefix(dir).unwrap().to_path_buf())
+            .collect()
+    }
+
+    /// Files of `dir` which `reference_dir` does not have. With the same
+    /// sectors written to both, these are leftovers, e.g. temporary files of
+    /// interrupted writes, whatever their names are. File names may depend
+    /// on the index and the contents of a sector, but not on the history of
+    /// writes.
+    pub fn unexpected_files(dir: &Path, reference_dir: &Path) -> Vec<PathBuf> {
+        let expected = StorageFootprint::of(reference_dir).relative_paths(reference_dir);
+        let mut unexpected: Vec<PathBuf> = StorageFootprint::of(dir)
+            .relative_paths(dir)
+            .into_iter()
+            .filter(|path| !expected.contains(path))
+            .collect();
+        unexpected.sort();
+        unexpected
+    }
+}
+
+/// How much a sectors manager may keep on disk. Defaults allow for a data
+/// and a metadata file per sector, plus a few bookkeeping files.
+pub struct StorageBudget {
+    pub files_per_sector: usize,
+    pub extra_files: usize,
+    pub bytes_per_sector: u64,
+    pub extra_bytes: u64,
+}
+
+impl Default for StorageBudget {
+    fn default() -> Self {
+        StorageBudget {
+            files_per_sector: 2,
+            extra_files: 4,
+            bytes_per_sector: 4096 + 512,
+            extra_bytes: 64 * 1024,
+        }
+    }
+}
+
+impl StorageBudget {
+    /// Describes how the storage directory exceeds the budget, if it does.
+    pub fn check_within(&self, dir: &Path, distinct_sectors: usize) -> Result<(), String> {
+        let footprint = StorageFootprint::of(dir);
+        let max_files = distinct_sectors * self.files_per_sector + self.extra_files;
+        let max_bytes = distinct_sectors as u64 * self.bytes_per_sector + self.extra_bytes;
+        if footprint.files_count() > max_files {
+            return Err(format!(
+                "{} files for {} written sectors, at most {} allowed",
+                footprint.files_count(),
+                distinct_sectors,
+                max_files
+            ));
+        }
+        if footprint.total_bytes() > max_bytes {
+            return Err(format!(
+                "{} bytes for {} written sectors, at most {} allowed",
+                footprint.total_bytes(),
+                distinct_sectors,
+                max_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn assert_within(&self, dir: &Path, distinct_sectors: usize) {
+        if let Err(err) = self.check_within(dir, distinct_sectors) {
+            panic!("{}", err);
+        }
+    }
+}
+
+pub struct CrashingWorkloadConfig {
+    pub seed: u64,
+    pub writes: usize,
+    pub sectors: std::ops::Range<u64>,
+    /// Chance of dropping the sectors manager and building it again
+    /// between two writes.
+    pub crash_probability: f64,
+    /// Chance of aborting a write midway, which is followed by a crash.
+    pub torn_write_probability: f64,
+}
+
+type Sector = (SectorVec, u64, u8);
+
+/// Runs random writes and overwrites through `build_sectors_manager`,
+/// crashing it now and then. After every recovery the storage directory is
+/// checked against the budget, and every written sector is read back.
+pub struct CrashingWorkload {
+    dir: PathBuf,
+    config: CrashingWorkloadConfig,
+    budget: StorageBudget,
+    rng: StdRng,
+    /// Values a sector may hold, more than one after a torn write
+    allowed: HashMap<u64, Vec<Sector>>,
+    pub recoveries: usize,
+    pub torn_writes: usize,
+}
+
+impl CrashingWorkload {
+    pub fn new(dir: PathBuf, config: CrashingWorkloadConfig, budget: StorageBudget) -> Self {
+        CrashingWorkload {
+            dir,
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            budget,
+            allowed: HashMap::new(),
+            recoveries: 0,
+            torn_writes: 0,
+        }
+    }
+
+    pub fn distinct_sectors(&self) -> usize {
+        self.allowed.len()
+    }
+
+    pub async fn run(&mut self) {
+        let mut sectors_manager = build_sectors_manager(self.dir.clone()).await;
+        for write_idx in 0..self.config.writes {
+            let sector_idx = self.rng.gen_range(self.config.sectors.clone());
+            let sector = (
+                SectorVec((0..4096).map(|_| self.rng.gen()).collect()),
+                write_idx as u64 + 1,
+                self.rng.gen_range(1..=8),
+            );
+
+            if self.rng.gen_bool(self.config.torn_write_probability) {
+                self.torn_write(sectors_manager, sector_idx, sector).await;
+                sectors_manager = self.recover().await;
+                continue;
+            }
+
+            sectors_manager.write(sector_idx, &sector).await;
+            self.allowed.insert(sector_idx, vec![sector]);
+
+            if self.rng.gen_bool(self.config.crash_probability) {
+                drop(sectors_manager);
+                sectors_manager = self.recover().await;
+            }
+        }
+        drop(sectors_manager);
+        self.recover().await;
+    }
+
+    async fn torn_write(
+        &mut self,
+        sectors_manager: Arc<dyn SectorsManager>,
+        sector_idx: u64,
+        sector: Sector,
+    ) {
+        self.torn_writes += 1;
+        let yields = self.rng.gen_range(0..16);
+        let written = sector.clone();
+        let write = tokio::spawn(async move {
+            sectors_manager.write(sector_idx, &written).await;
+        });
+        for _ in 0..yields {
+            tokio::task::yield_now().await;
+        }
+        write.abort();
+        let _ = write.await;
+        // Blocking file operations already started cannot be cancelled,
+        // let them land before "restarting"
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let allowed = self.allowed.entry(sector_idx).or_default();
+        if allowed.is_empty() {
+            allowed.push((SectorVec(vec![0; 4096]), 0, 0));
+        }
+        allowed.push(sector);
+    }
+
+    async fn recover(&mut self) -> Arc<dyn SectorsManager> {
+        self.recoveries += 1;
+        let sectors_manager = build_sectors_manager(self.dir.clone()).await;
+
+        if let Err(err) = self.budget.check_within(&self.dir, self.allowed.len()) {
+            panic!("{} after recovery (seed {})", err, self.config.seed);
+        }
+        for (sector_idx, allowed) in self.allowed.iter_mut() {
+            let data = sectors_manager.read_data(*sector_idx).await;
+            let (timestamp, write_rank) = sectors_manager.read_metadata(*sector_idx).await;
+            let found = allowed
+                .iter()
+                .find(|(allowed_data, allowed_timestamp, allowed_rank)| {
+                    *allowed_data == data
+                        && *allowed_timestamp == timestamp
+                        && *allowed_rank == write_rank
+                })
+                .cloned()
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Sector {} holds an unexpected value after recovery (seed {})",
+                        sector_idx, self.config.seed
+                    )
+                });
+            *allowed = vec![found];
+        }
+        self.assert_no_leftover_files().await;
+        sectors_manager
+    }
+
+    /// Writes the sectors as they are now to a fresh directory, without
+    /// crashes, and fails if the storage directory has any file that one
+    /// does not.
+    pub async fn assert_no_leftover_files(&self) {
+        let reference_dir = tempfile::tempdir().unwrap();
+        let reference = build_sectors_manager(reference_dir.path().to_path_buf()).await;
+        for (sector_idx, allowed) in &self.allowed {
+            let sector = &allowed[0];
+            /* A torn first write may leave the sector as if never written */
+            if sector.1 != 0 {
+                reference.write(*sector_idx, sector).await;
+            }
+        }
+        drop(reference);
+
+        let unexpected = StorageFootprint::unexpected_files(&self.dir, reference_dir.path());
+        assert!(
+            unexpected.is_empty(),
+            "Files left after recovery which a directory with the same sectors does not have: {:?} (seed {})",
+            unexpected,
+            self.config.seed
+        );
+    }
+
+    /// `StorageBudget::assert_within` for the sectors written so far.
+    pub fn assert_within_budget(&self) {
+        if let Err(err) = self.budget.check_within(&self.dir, self.allowed.len()) {
+            panic!("{} (seed {})", err, self.config.seed);
+        }
+    }
+}
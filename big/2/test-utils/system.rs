@@ -1,4 +1,6 @@
 use std::convert::TryInto;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Once;
 
 use assignment_2_solution::{
     run_register_process, serialize_register_command, ClientRegisterCommand, Configuration,
@@ -8,7 +10,7 @@ use hmac::{Hmac, Mac};
 use rand::Rng;
 use sha2::Sha256;
 use tempfile::TempDir;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time::Duration;
 
@@ -20,15 +22,6 @@ pub struct RegisterResponse {
     pub hmac_tag: [u8; HMAC_TAG_SIZE],
 }
 
-impl RegisterResponse {
-    fn msg_type(&self) -> u8 {
-        match self.content {
-            RegisterResponseContent::Read(_) => 0x41,
-            RegisterResponseContent::Write => 0x42,
-        }
-    }
-}
-
 pub struct RegisterResponseHeader {
     pub status_code: StatusCode,
     pub request_identifier: u64,
@@ -44,6 +37,7 @@ pub struct TestProcessesConfig {
     pub hmac_system_key: Vec<u8>,
     storage_dirs: Vec<TempDir>,
     pub tcp_locations: Vec<(String, u16)>,
+    n_sectors: u64,
 }
 
 impl TestProcessesConfig {
@@ -63,9 +57,45 @@ impl TestProcessesConfig {
             tcp_locations: (0..processes_count)
                 .map(|idx| ("localhost".to_string(), port_range_start + idx as u16))
                 .collect(),
+            n_sectors: TestProcessesConfig::N_SECTORS,
         }
     }
 
+    /// Configuration of a cluster started elsewhere, e.g. read from an
+    /// `atomic_disc_drive` config file. It has no storage directories, so
+    /// it can only be used to connect to the processes, not to start them.
+    pub fn of_running_cluster(
+        hmac_system_key: Vec<u8>,
+        hmac_client_key: Vec<u8>,
+        tcp_locations: Vec<(String, u16)>,
+        n_sectors: u64,
+    ) -> Self {
+        TestProcessesConfig {
+            hmac_client_key,
+            hmac_system_key,
+            storage_dirs: Vec::new(),
+            tcp_locations,
+            n_sectors,
+        }
+    }
+
+    /// Fixed keys instead of random ones, e.g. the golden ones, so that the
+    /// frames of the processes are known in advance.
+    pub fn with_hmac_keys(mut self, hmac_system_key: &[u8], hmac_client_key: &[u8]) -> Self {
+        self.hmac_system_key = hmac_system_key.to_vec();
+        self.hmac_client_key = hmac_client_key.to_vec();
+        self
+    }
+
+    pub fn with_n_sectors(mut self, n_sectors: u64) -> Self {
+        self.n_sectors = n_sectors;
+        self
+    }
+
+    pub fn n_sectors(&self) -> u64 {
+        self.n_sectors
+    }
+
     pub fn config(&self, proc_idx: usize) -> Configuration {
         Configuration {
             public: PublicConfiguration {
@@ -77,7 +107,7 @@ impl TestProcessesConfig {
                     .to_path_buf(),
                 tcp_locations: self.tcp_locations.clone(),
                 self_rank: (proc_idx + 1) as u8,
-                n_sectors: TestProcessesConfig::N_SECTORS,
+                n_sectors: self.n_sectors,
             },
             hmac_system_key: self.hmac_system_key.clone().try_into().unwrap(),
             hmac_client_key: self.hmac_client_key.clone().try_into().unwrap(),
@@ -85,8 +115,13 @@ impl TestProcessesConfig {
     }
 
     pub async fn start(&self) {
-        let processes_count = self.storage_dirs.len();
-        for idx in 0..processes_count {
+        self.start_some(0..self.storage_dirs.len()).await;
+    }
+
+    /// Starts only the given processes, so that the remaining ones can be
+    /// replaced by stubs.
+    pub async fn start_some(&self, proc_idxs: impl IntoIterator<Item = usize>) {
+        for idx in proc_idxs {
             tokio::spawn(run_register_process(self.config(idx)));
         }
         wait_for_tcp_listen().await;
@@ -108,9 +143,15 @@ impl TestProcessesConfig {
             .expect("Could not connect to TCP port")
     }
 
-    pub async fn read_response(&self, stream: &mut TcpStream) -> Result<RegisterResponse, String> {
+    pub async fn read_response<S: AsyncRead + Unpin>(
+        &self,
+        stream: &mut S,
+    ) -> Result<RegisterResponse, String> {
         let mut buf = [0; 8];
-        stream.read_exact(&mut buf).await.unwrap();
+        stream
+            .read_exact(&mut buf)
+            .await
+            .map_err(|err| err.to_string())?;
         if &buf[0..4] != MAGIC_NUMBER.as_ref() {
             return Err("Invalid magic number".to_string());
         }
@@ -121,7 +162,10 @@ impl TestProcessesConfig {
         }
 
         let status_code = status_code.unwrap();
-        stream.read_exact(&mut buf).await.unwrap();
+        stream
+            .read_exact(&mut buf)
+            .await
+            .map_err(|err| err.to_string())?;
         let request_number = u64::from_be_bytes(buf);
         let header = RegisterResponseHeader {
             status_code,
@@ -130,7 +174,10 @@ impl TestProcessesConfig {
         let mut hmac_tag = [0; HMAC_TAG_SIZE];
         match msg_type {
             66 => {
-                stream.read_exact(&mut hmac_tag).await.unwrap();
+                stream
+                    .read_exact(&mut hmac_tag)
+                    .await
+                    .map_err(|err| err.to_string())?;
                 Ok(RegisterResponse {
                     header,
                     content: RegisterResponseContent::Write,
@@ -139,8 +186,14 @@ impl TestProcessesConfig {
             }
             65 => {
                 let mut sector = vec![0; 4096];
-                stream.read_exact(&mut sector).await.unwrap();
-                stream.read_exact(&mut hmac_tag).await.unwrap();
+                stream
+                    .read_exact(&mut sector)
+                    .await
+                    .map_err(|err| err.to_string())?;
+                stream
+                    .read_exact(&mut hmac_tag)
+                    .await
+                    .map_err(|err| err.to_string())?;
                 Ok(RegisterResponse {
                     header,
                     content: RegisterResponseContent::Read(SectorVec(sector)),
@@ -160,20 +213,48 @@ impl TestProcessesConfig {
     }
 
     fn hmac_tag_is_ok(&self, response: &RegisterResponse) -> bool {
-        let msg_type = response.msg_type();
-        let mut data = vec![];
-        data.extend_from_slice(MAGIC_NUMBER.as_ref());
-        data.extend(&[0, 0, response.header.status_code as u8, msg_type]);
-        data.extend(&response.header.request_identifier.to_be_bytes());
-        match &response.content {
-            RegisterResponseContent::Read(SectorVec(sector)) => data.extend(sector),
-            RegisterResponseContent::Write => {}
-        }
+        response_hmac_tag_is_ok(response, &self.hmac_client_key)
+    }
+}
+
+pub fn response_hmac_tag_is_ok(response: &RegisterResponse, hmac_client_key: &[u8]) -> bool {
+    let data = encode_response_without_tag(&response.header, &response.content);
+    let mut mac = HmacSha256::new_from_slice(hmac_client_key).unwrap();
+    mac.update(&data);
+    mac.verify_slice(&response.hmac_tag).is_ok()
+}
 
-        let mut mac = HmacSha256::new_from_slice(&self.hmac_client_key).unwrap();
-        mac.update(&data);
-        mac.verify_slice(&response.hmac_tag).is_ok()
+/// Counterpart of `TestProcessesConfig::read_response`: the bytes a process
+/// is expected to send back to a client, including the HMAC tag.
+pub fn encode_response(
+    header: &RegisterResponseHeader,
+    content: &RegisterResponseContent,
+    hmac_client_key: &[u8],
+) -> Vec<u8> {
+    let mut data = encode_response_without_tag(header, content);
+    let mut mac = HmacSha256::new_from_slice(hmac_client_key).unwrap();
+    mac.update(&data);
+    data.extend(mac.finalize().into_bytes());
+    data
+}
+
+fn encode_response_without_tag(
+    header: &RegisterResponseHeader,
+    content: &RegisterResponseContent,
+) -> Vec<u8> {
+    let msg_type = match content {
+        RegisterResponseContent::Read(_) => 0x41,
+        RegisterResponseContent::Write => 0x42,
+    };
+    let mut data = vec![];
+    data.extend_from_slice(MAGIC_NUMBER.as_ref());
+    data.extend(&[0, 0, header.status_code as u8, msg_type]);
+    data.extend(&header.request_identifier.to_be_bytes());
+    match content {
+        RegisterResponseContent::Read(SectorVec(sector)) => data.extend(sector),
+        RegisterResponseContent::Write => {}
     }
+    data
 }
 
 fn try_to_status_code(byte: u8) -> Option<StatusCode> {
@@ -185,6 +266,40 @@ fn try_to_status_code(byte: u8) -> Option<StatusCode> {
     }
 }
 
+static PANICS_SEEN: AtomicUsize = AtomicUsize::new(0);
+static PANIC_HOOK: Once = Once::new();
+
+/// Counts panics in every thread of the test binary, including the ones in
+/// tasks spawned by the processes, which tokio would swallow otherwise.
+/// Tests using it should be `#[serial]`, as panics of tests running in
+/// parallel are counted too.
+pub struct PanicWatch {
+    seen_at_start: usize,
+}
+
+impl PanicWatch {
+    pub fn start() -> Self {
+        PANIC_HOOK.call_once(|| {
+            let previous_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                PANICS_SEEN.fetch_add(1, Ordering::SeqCst);
+                previous_hook(info);
+            }));
+        });
+        PanicWatch {
+            seen_at_start: PANICS_SEEN.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn panics(&self) -> usize {
+        PANICS_SEEN.load(Ordering::SeqCst) - self.seen_at_start
+    }
+
+    pub fn assert_no_panics(&self) {
+        assert_eq!(self.panics(), 0, "Some process panicked");
+    }
+}
+
 async fn wait_for_tcp_listen() {
     tokio::time::sleep(Duration::from_millis(300)).await;
 }